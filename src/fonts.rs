@@ -0,0 +1,224 @@
+// --- Fonts ---
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A font face picked in the settings or found on the system.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FontChoice {
+    // Shown in the settings
+    pub name: String,
+    pub path: PathBuf,
+    // Face within a collection (.ttc)
+    #[serde(default)]
+    pub index: u32,
+}
+
+impl FontChoice {
+    /// A font picked from a file dialog, named after the file.
+    pub fn from_file(path: PathBuf) -> Self {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        Self { name, path, index: 0 }
+    }
+}
+
+/// Family used by desktop lyrics and the lyrics panel. Starts with the lyrics
+/// font, if one is set, and falls back to the UI fonts.
+pub fn lyrics_family() -> egui::FontFamily {
+    egui::FontFamily::Name("lyrics".into())
+}
+
+const FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+// Fonts with Chinese glyphs in their usual install locations, best first.
+// Noto CJK collections hold JP, KR, SC, TC and HK faces; index 2 is SC.
+#[cfg(target_os = "windows")]
+const CJK_CANDIDATES: &[(&str, u32)] = &[
+    ("C:\\Windows\\Fonts\\msyh.ttc", 0),
+    ("C:\\Windows\\Fonts\\msyh.ttf", 0),
+    ("C:\\Windows\\Fonts\\simhei.ttf", 0),
+    ("C:\\Windows\\Fonts\\simsun.ttc", 0),
+];
+#[cfg(target_os = "macos")]
+const CJK_CANDIDATES: &[(&str, u32)] = &[
+    ("/System/Library/Fonts/PingFang.ttc", 0),
+    ("/System/Library/Fonts/Hiragino Sans GB.ttc", 0),
+    ("/System/Library/Fonts/STHeiti Medium.ttc", 0),
+    ("/Library/Fonts/Arial Unicode.ttf", 0),
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const CJK_CANDIDATES: &[(&str, u32)] = &[
+    ("/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc", 2),
+    ("/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc", 2),
+    ("/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc", 2),
+    ("/usr/share/fonts/opentype/noto/NotoSerifCJK-Regular.ttc", 2),
+    ("/usr/share/fonts/truetype/wqy/wqy-microhei.ttc", 0),
+    ("/usr/share/fonts/truetype/wqy/wqy-zenhei.ttc", 0),
+    ("/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc", 0),
+    ("/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf", 0),
+];
+
+/// Finds a font that can show Chinese. Fontconfig is asked first since it
+/// knows about user-installed fonts and the user's preferences.
+pub fn discover_cjk() -> Option<FontChoice> {
+    fc_match_cjk().or_else(|| {
+        CJK_CANDIDATES.iter()
+            .map(|&(path, index)| (Path::new(path), index))
+            .find(|(path, _)| path.exists())
+            .map(|(path, index)| FontChoice { index, ..FontChoice::from_file(path.to_path_buf()) })
+    })
+}
+
+fn fc_match_cjk() -> Option<FontChoice> {
+    let output = Command::new("fc-match")
+        .args(["--format", "%{family[0]}\t%{index}\t%{file}\t%{lang}", "sans-serif:lang=zh-cn"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut fields = text.split('\t');
+    let (name, index, file, langs) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+    // fc-match always returns its best guess, even a font without CJK glyphs
+    if !langs.split('|').any(|lang| lang == "zh-cn") || !has_font_extension(Path::new(file)) {
+        return None;
+    }
+    Some(FontChoice { name: name.to_string(), path: PathBuf::from(file), index: index.parse().unwrap_or(0) })
+}
+
+/// Installed fonts for the settings, sorted by name. Uses fontconfig where
+/// available, otherwise scans the usual font folders.
+pub fn list_system_fonts() -> Vec<FontChoice> {
+    let mut fonts = fc_list().unwrap_or_else(|| {
+        let mut fonts = Vec::new();
+        for dir in font_dirs() {
+            scan_dir(&dir, &mut fonts);
+        }
+        fonts
+    });
+    fonts.sort_by_cached_key(|font| font.name.to_lowercase());
+    fonts.dedup_by(|a, b| a.path == b.path && a.index == b.index);
+    fonts
+}
+
+fn fc_list() -> Option<Vec<FontChoice>> {
+    let output = Command::new("fc-list")
+        .args([":scalable=true", "--format", "%{family[0]}\t%{style[0]}\t%{index}\t%{file}\n"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let fonts = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let (family, style, index, file) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+            let path = PathBuf::from(file);
+            if !has_font_extension(&path) {
+                return None; // Type 1 and bitmap fonts can't be loaded
+            }
+            let name = match style {
+                "" | "Regular" | "Book" | "Normal" => family.to_string(),
+                _ => format!("{} {}", family, style),
+            };
+            Some(FontChoice { name, path, index: index.parse().unwrap_or(0) })
+        })
+        .collect();
+    Some(fonts)
+}
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut folders = Vec::new();
+    if cfg!(target_os = "windows") {
+        let windir = std::env::var_os("WINDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+        folders.push(windir.join("Fonts"));
+        if let Some(local) = dirs::data_local_dir() {
+            folders.push(local.join("Microsoft").join("Windows").join("Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        folders.extend(["/System/Library/Fonts", "/Library/Fonts"].map(PathBuf::from));
+    } else {
+        folders.extend(["/usr/share/fonts", "/usr/local/share/fonts"].map(PathBuf::from));
+        if let Some(home) = dirs::home_dir() {
+            folders.push(home.join(".fonts"));
+        }
+    }
+    folders.extend(dirs::font_dir());
+    folders
+}
+
+fn scan_dir(dir: &Path, fonts: &mut Vec<FontChoice>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        // Symlinked folders aren't followed, so a link back up can't loop
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            scan_dir(&path, fonts);
+        } else if has_font_extension(&path) {
+            fonts.push(FontChoice::from_file(path));
+        }
+    }
+}
+
+fn has_font_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.as_str()))
+}
+
+// --- Loading ---
+
+// egui panics on data it can't parse, so files are sniffed first: TrueType,
+// OpenType/CFF or a collection with enough faces for the index
+fn load(font: &FontChoice) -> Result<egui::FontData, String> {
+    let bytes = std::fs::read(&font.path).map_err(|e| format!("{:?}: {}", font.path, e))?;
+    let valid = match bytes.get(..4) {
+        Some([0, 1, 0, 0] | b"OTTO" | b"true") => font.index == 0,
+        Some(b"ttcf") => bytes.get(8..12)
+            .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+            .is_some_and(|faces| font.index < faces),
+        _ => false,
+    };
+    if !valid {
+        return Err(format!("{:?}: not a TrueType / OpenType font", font.path));
+    }
+    let mut data = egui::FontData::from_owned(bytes);
+    data.index = font.index;
+    Ok(data)
+}
+
+/// Sets up egui's fonts: the UI font (or a discovered CJK font when none is
+/// chosen) ahead of egui's defaults, and the lyrics family. Fonts that fail to
+/// load are skipped and reported.
+pub fn install(ctx: &egui::Context, ui_font: Option<&FontChoice>, lyrics_font: Option<&FontChoice>) -> Vec<String> {
+    let mut fonts = egui::FontDefinitions::default();
+    let mut errors = Vec::new();
+
+    let ui_font = ui_font.cloned().or_else(discover_cjk);
+    if let Some(font) = ui_font {
+        match load(&font) {
+            Ok(data) => {
+                fonts.font_data.insert("ui".to_owned(), data.into());
+                fonts.families.get_mut(&egui::FontFamily::Proportional).unwrap().insert(0, "ui".to_owned());
+                fonts.families.get_mut(&egui::FontFamily::Monospace).unwrap().push("ui".to_owned());
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut lyrics = fonts.families[&egui::FontFamily::Proportional].clone();
+    if let Some(font) = lyrics_font {
+        match load(font) {
+            Ok(data) => {
+                fonts.font_data.insert("lyrics".to_owned(), data.into());
+                lyrics.insert(0, "lyrics".to_owned());
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    fonts.families.insert(lyrics_family(), lyrics);
+
+    ctx.set_fonts(fonts);
+    errors
+}
@@ -0,0 +1,132 @@
+// --- Localization ---
+
+use super::paths;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+// Compiled in, so the player works without any files next to it
+const BUILT_IN: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.json")),
+    ("zh", include_str!("../locales/zh.json")),
+];
+
+// Used for strings a language file doesn't have
+const FALLBACK: &str = "en";
+
+/// A language file: `<config dir>/locales/<code>.json`, e.g. `de.json`. A file
+/// with the code of a built-in language overrides some of its strings.
+#[derive(Deserialize)]
+struct LocaleFile {
+    // Shown in the language menu, in the language itself
+    name: String,
+    #[serde(default)]
+    strings: HashMap<String, String>,
+}
+
+struct Locale {
+    code: String,
+    name: String,
+    strings: HashMap<String, String>,
+}
+
+static LOCALES: OnceLock<Vec<Locale>> = OnceLock::new();
+
+fn locales() -> &'static [Locale] {
+    LOCALES.get_or_init(built_in)
+}
+
+fn built_in() -> Vec<Locale> {
+    BUILT_IN.iter()
+        .map(|(code, json)| {
+            let file: LocaleFile = serde_json::from_str(json).expect("built-in language file");
+            Locale { code: code.to_string(), name: file.name, strings: file.strings }
+        })
+        .collect()
+}
+
+/// Loads the built-in languages plus any files in `<config dir>/locales`.
+/// Call once at startup; returns a message for each file that couldn't be read.
+pub fn init() -> Vec<String> {
+    let mut locales = built_in();
+    let mut errors = Vec::new();
+    let dir = paths::config_file("locales");
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")))
+        .collect();
+    files.sort();
+    for path in files {
+        let code = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let file = match std::fs::read_to_string(&path).map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<LocaleFile>(&text).map_err(|e| e.to_string()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(format!("{:?}: {}", path, e));
+                continue;
+            }
+        };
+        match locales.iter_mut().find(|l| l.code == code) {
+            Some(locale) => {
+                locale.name = file.name;
+                locale.strings.extend(file.strings);
+            }
+            None => locales.push(Locale { code, name: file.name, strings: file.strings }),
+        }
+    }
+    if LOCALES.set(locales).is_err() {
+        eprintln!("Languages were used before they were loaded");
+    }
+    errors
+}
+
+/// A loaded language. Cheap to copy around like the old two-variant enum.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Language(usize);
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::from_code("zh").unwrap_or(Language(0))
+    }
+}
+
+impl Language {
+    pub fn from_code(code: &str) -> Option<Self> {
+        locales().iter().position(|l| l.code == code).map(Language)
+    }
+
+    pub fn all() -> impl Iterator<Item = Language> {
+        (0..locales().len()).map(Language)
+    }
+
+    pub fn code(self) -> &'static str {
+        &locales()[self.0].code
+    }
+
+    pub fn name(self) -> &'static str {
+        &locales()[self.0].name
+    }
+
+    /// The string for `key`, falling back to English and then the key itself.
+    pub fn tr(self, key: &'static str) -> &'static str {
+        let locales = locales();
+        let fallback = locales.iter().find(|l| l.code == FALLBACK);
+        locales[self.0].strings.get(key)
+            .or_else(|| fallback.and_then(|l| l.strings.get(key)))
+            .map_or(key, String::as_str)
+    }
+
+    /// Like `tr`, with `{name}` placeholders filled in from `args`.
+    pub fn trf(self, key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+        let mut text = self.tr(key).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+}
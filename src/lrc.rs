@@ -0,0 +1,543 @@
+// --- LRC Parsing ---
+
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+
+// Times go over the API as whole milliseconds
+fn as_millis<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(time.as_millis() as u64)
+}
+
+fn as_millis_opt<S: Serializer>(time: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_some(&(time.as_millis() as u64)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// ID tags from the head of an LRC file (`[ti:...]`, `[ar:...]`, ...).
+#[derive(Serialize, Clone, Default, PartialEq, Debug)]
+pub struct LrcMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    // [au:] - songwriter
+    pub author: Option<String>,
+    // [by:] - author of the LRC file itself
+    pub creator: Option<String>,
+    #[serde(rename = "length_ms", serialize_with = "as_millis_opt")]
+    pub length: Option<Duration>,
+    // [offset:] in milliseconds; positive values make lyrics appear sooner.
+    // Already applied to the line times.
+    pub offset_ms: i64,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct LyricLine {
+    #[serde(rename = "time_ms", serialize_with = "as_millis")]
+    pub time: Duration,
+    pub text: String,
+    // Word timings from enhanced LRC (`<mm:ss.xx>word`); empty for plain lines.
+    // The words' texts concatenate to `text`; a trailing word with empty text
+    // marks when the last one ends.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<LyricWord>,
+    // A second line with the same timestamp, or the matching line of a
+    // separate translation file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct LyricWord {
+    #[serde(rename = "time_ms", serialize_with = "as_millis")]
+    pub time: Duration,
+    pub text: String,
+}
+
+impl LyricLine {
+    /// How far the karaoke highlight has got at `position`, in characters of
+    /// `text` (fractional within the word being sung). `line_end` is used as the
+    /// end of the last word if the line has no closing timestamp. Returns `None`
+    /// for lines without word timings.
+    pub fn highlight_chars(&self, position: Duration, line_end: Option<Duration>) -> Option<f32> {
+        if self.words.is_empty() {
+            return None;
+        }
+        let mut chars_before = 0;
+        for (i, word) in self.words.iter().enumerate() {
+            let len = word.text.chars().count();
+            let end = match self.words.get(i + 1) {
+                Some(next) => next.time,
+                None => line_end.unwrap_or(word.time + Duration::from_secs(1)),
+            };
+            if position < word.time {
+                break;
+            }
+            if position < end {
+                let fraction = (position - word.time).as_secs_f32() / (end - word.time).as_secs_f32();
+                return Some(chars_before as f32 + fraction * len as f32);
+            }
+            chars_before += len;
+        }
+        Some(chars_before as f32)
+    }
+}
+
+#[derive(Serialize, Clone, Default, PartialEq, Debug)]
+pub struct Lyrics {
+    pub metadata: LrcMetadata,
+    // Sorted by time; lines sharing a timestamp keep their file order
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Index of the line showing at `position`, if any line has started yet.
+    pub fn line_at(&self, position: Duration) -> Option<usize> {
+        self.lines.partition_point(|line| line.time <= position).checked_sub(1)
+    }
+
+    /// Attaches the lines of a separate translation file to the lines starting
+    /// at (nearly) the same time. Returns how many lines got a translation.
+    pub fn merge_translation(&mut self, translation: &Lyrics) -> usize {
+        let mut merged = 0;
+        for line in translation.lines.iter().filter(|l| !l.text.is_empty()) {
+            let start = self.lines.partition_point(|l| l.time + TRANSLATION_TOLERANCE < line.time);
+            let target = self.lines[start..].iter_mut()
+                .take_while(|l| l.time <= line.time + TRANSLATION_TOLERANCE)
+                .find(|l| l.translation.is_none() && !l.text.is_empty());
+            if let Some(target) = target {
+                target.translation = Some(line.text.clone());
+                merged += 1;
+            }
+        }
+        merged
+    }
+
+    /// Writes the lyrics back out as LRC. The offset is already part of the
+    /// line times, so no `[offset:]` tag is written; translations become a
+    /// second line with the same timestamp.
+    pub fn to_lrc(&self) -> String {
+        let mut out = String::new();
+        let meta = &self.metadata;
+        let tags = [("ti", &meta.title), ("ar", &meta.artist), ("al", &meta.album), ("au", &meta.author), ("by", &meta.creator)];
+        for (key, value) in tags {
+            if let Some(value) = value {
+                out.push_str(&format!("[{}:{}]\n", key, value));
+            }
+        }
+        if let Some(length) = meta.length {
+            let secs = length.as_secs();
+            out.push_str(&format!("[length:{:02}:{:02}]\n", secs / 60, secs % 60));
+        }
+        for line in &self.lines {
+            out.push_str(&format!("[{}]", format_timestamp(line.time)));
+            if line.words.is_empty() {
+                out.push_str(&line.text);
+            } else {
+                for word in &line.words {
+                    out.push_str(&format!("<{}>{}", format_timestamp(word.time), word.text));
+                }
+            }
+            out.push('\n');
+            if let Some(translation) = &line.translation {
+                out.push_str(&format!("[{}]{}\n", format_timestamp(line.time), translation));
+            }
+        }
+        out
+    }
+}
+
+/// `mm:ss.xx`, the usual LRC timestamp precision.
+pub fn format_timestamp(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{:02}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+// Translation files are often timed independently and a few ms off
+const TRANSLATION_TOLERANCE: Duration = Duration::from_millis(100);
+
+/// Parses LRC text. Lines may carry several timestamps (`[00:12.00][01:30.00]chorus`),
+/// which become one lyric line each. Lines without a timestamp or ID tag, and
+/// tags that can't be read, are skipped rather than failing the whole file.
+pub fn parse_lrc(content: &str) -> Lyrics {
+    let mut lyrics = Lyrics::default();
+    let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+
+    for line in content.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[') {
+            let Some(end) = tag.find(']') else { break };
+            let tag = &tag[..end];
+            if let Some(time) = parse_timestamp(tag) {
+                times.push(time);
+            } else if times.is_empty() && apply_id_tag(&mut lyrics.metadata, tag) {
+                // ID tags take the whole line
+                rest = "";
+                break;
+            } else {
+                // Not a tag we know; treat it as part of the text
+                break;
+            }
+            rest = rest[end + 2..].trim_start();
+        }
+        let Some(&first_time) = times.first() else { continue };
+        let (text, words) = parse_words(rest.trim(), first_time);
+        for time in times {
+            // Word times are written for the first timestamp; move them along
+            // with the line for repeats
+            let words = words.iter()
+                .map(|w| LyricWord { time: (w.time + time).saturating_sub(first_time), text: w.text.clone() })
+                .collect();
+            lyrics.lines.push(LyricLine { time, text: text.clone(), words, translation: None });
+        }
+    }
+
+    let offset = lyrics.metadata.offset_ms;
+    if offset != 0 {
+        let shift = Duration::from_millis(offset.unsigned_abs());
+        let apply = |time: Duration| if offset > 0 { time.saturating_sub(shift) } else { time + shift };
+        for line in &mut lyrics.lines {
+            line.time = apply(line.time);
+            for word in &mut line.words {
+                word.time = apply(word.time);
+            }
+        }
+    }
+    // Stable, so lines sharing a timestamp stay in file order
+    lyrics.lines.sort_by_key(|line| line.time);
+    group_translations(&mut lyrics.lines);
+    lyrics
+}
+
+// Bilingual files repeat each timestamp: the original line, then its
+// translation. Folds the second of such a pair into the first.
+fn group_translations(lines: &mut Vec<LyricLine>) {
+    let mut grouped: Vec<LyricLine> = Vec::with_capacity(lines.len());
+    for line in lines.drain(..) {
+        if let Some(prev) = grouped.last_mut() {
+            if prev.time == line.time && prev.translation.is_none() && !prev.text.is_empty() && !line.text.is_empty() {
+                prev.translation = Some(line.text);
+                continue;
+            }
+        }
+        grouped.push(line);
+    }
+    *lines = grouped;
+}
+
+/// Splits enhanced LRC text (`<00:01.00>Hello <00:01.50>world<00:02.00>`) into
+/// timed words. Text before the first word tag starts at `line_time`. Returns
+/// the plain text and the words, or no words if the text has no word tags.
+fn parse_words(text: &str, line_time: Duration) -> (String, Vec<LyricWord>) {
+    let mut words = Vec::new();
+    let mut current = LyricWord { time: line_time, text: String::new() };
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let tag_time = rest[start + 1..].find('>').and_then(|end| {
+            parse_timestamp(&rest[start + 1..start + 1 + end]).map(|time| (time, start + end + 2))
+        });
+        match tag_time {
+            Some((time, after)) => {
+                current.text.push_str(&rest[..start]);
+                let next = LyricWord { time, text: String::new() };
+                words.push(std::mem::replace(&mut current, next));
+                rest = &rest[after..];
+            }
+            None => {
+                // A literal '<'
+                current.text.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    current.text.push_str(rest);
+    words.push(current);
+
+    if words.len() == 1 {
+        return (text.to_string(), Vec::new());
+    }
+    // Keep a trailing empty word (the end marker), drop other empty ones such
+    // as the lead-in before a tag at the very start
+    let end_marker = words.pop_if(|w| w.text.is_empty());
+    words.retain(|w| !w.text.is_empty());
+    words.extend(end_marker);
+    if let Some(first) = words.first_mut() {
+        first.text = first.text.trim_start().to_string();
+    }
+    if let Some(last) = words.iter_mut().rev().find(|w| !w.text.is_empty()) {
+        last.text = last.text.trim_end().to_string();
+    }
+    let text = words.iter().map(|w| w.text.as_str()).collect();
+    (text, words)
+}
+
+/// `mm:ss`, `mm:ss.x`, `mm:ss.xx`, `mm:ss.xxx` (also with `:` before the
+/// fraction, as some editors write). Minutes may exceed 59.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (min, rest) = tag.split_once(':')?;
+    let (sec, fraction) = match rest.find(['.', ':']) {
+        Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+        None => (rest, None),
+    };
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(min) || !all_digits(sec) {
+        return None;
+    }
+    let min: u64 = min.parse().ok()?;
+    let sec: u64 = sec.parse().ok()?;
+    if sec >= 60 {
+        return None;
+    }
+    let millis = match fraction {
+        None => 0,
+        Some(f) if all_digits(f) => {
+            // Scale to milliseconds: .5 = 500ms, .05 = 50ms, .005 = 5ms;
+            // anything past millisecond precision is dropped
+            let digits = &f[..f.len().min(3)];
+            digits.parse::<u64>().ok()? * 10u64.pow(3 - digits.len() as u32)
+        }
+        Some(_) => return None,
+    };
+    Some(Duration::from_secs(min.checked_mul(60)? + sec) + Duration::from_millis(millis))
+}
+
+// Returns false if `tag` isn't `key:value` with a known key
+fn apply_id_tag(metadata: &mut LrcMetadata, tag: &str) -> bool {
+    let Some((key, value)) = tag.split_once(':') else { return false };
+    let value = value.trim();
+    let text = || (!value.is_empty()).then(|| value.to_string());
+    match key.trim().to_ascii_lowercase().as_str() {
+        "ti" => metadata.title = text(),
+        "ar" => metadata.artist = text(),
+        "al" => metadata.album = text(),
+        "au" => metadata.author = text(),
+        "by" => metadata.creator = text(),
+        "length" => metadata.length = parse_timestamp(value),
+        "offset" => metadata.offset_ms = value.trim_start_matches('+').parse().unwrap_or(0),
+        // Known but unused
+        "re" | "ve" | "#" => {}
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn times_and_texts(lyrics: &Lyrics) -> Vec<(u64, &str)> {
+        lyrics.lines.iter().map(|l| (l.time.as_millis() as u64, l.text.as_str())).collect()
+    }
+
+    #[test]
+    fn parses_simple_lines() {
+        let lyrics = parse_lrc("[00:01.00]one\n[00:02.50]two\n");
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "one"), (2500, "two")]);
+    }
+
+    #[test]
+    fn fraction_precision() {
+        assert_eq!(parse_timestamp("00:01"), Some(ms(1000)));
+        assert_eq!(parse_timestamp("00:01.5"), Some(ms(1500)));
+        assert_eq!(parse_timestamp("00:01.05"), Some(ms(1050)));
+        assert_eq!(parse_timestamp("00:01.005"), Some(ms(1005)));
+        assert_eq!(parse_timestamp("00:01.0059"), Some(ms(1005)));
+        assert_eq!(parse_timestamp("00:01:50"), Some(ms(1500)));
+        assert_eq!(parse_timestamp("120:00.00"), Some(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn multiple_timestamps_per_line() {
+        let lyrics = parse_lrc("[00:12.00][01:30.00]chorus\n[00:20.00]verse\n");
+        assert_eq!(times_and_texts(&lyrics), vec![(12000, "chorus"), (20000, "verse"), (90000, "chorus")]);
+    }
+
+    #[test]
+    fn timestamps_separated_by_spaces() {
+        let lyrics = parse_lrc("[00:01.00] [00:03.00] text");
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "text"), (3000, "text")]);
+    }
+
+    #[test]
+    fn reads_metadata() {
+        let lyrics = parse_lrc("[ti:Song]\n[ar: Artist ]\n[al:Album]\n[au:Writer]\n[by:Me]\n[length: 03:25]\n[re:Editor]\n[00:01.00]hi");
+        let meta = &lyrics.metadata;
+        assert_eq!(meta.title.as_deref(), Some("Song"));
+        assert_eq!(meta.artist.as_deref(), Some("Artist"));
+        assert_eq!(meta.album.as_deref(), Some("Album"));
+        assert_eq!(meta.author.as_deref(), Some("Writer"));
+        assert_eq!(meta.creator.as_deref(), Some("Me"));
+        assert_eq!(meta.length, Some(Duration::from_secs(205)));
+        // Metadata never shows up as lyric lines
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "hi")]);
+    }
+
+    #[test]
+    fn empty_metadata_is_none() {
+        let lyrics = parse_lrc("[ti:]\n[ar:   ]");
+        assert_eq!(lyrics.metadata, LrcMetadata::default());
+    }
+
+    #[test]
+    fn positive_offset_makes_lyrics_sooner() {
+        let lyrics = parse_lrc("[offset:+500]\n[00:01.00]a\n[00:00.20]b");
+        assert_eq!(lyrics.metadata.offset_ms, 500);
+        assert_eq!(times_and_texts(&lyrics), vec![(0, "b"), (500, "a")]);
+    }
+
+    #[test]
+    fn negative_offset_makes_lyrics_later() {
+        let lyrics = parse_lrc("[00:01.00]a\n[offset:-250]");
+        assert_eq!(lyrics.metadata.offset_ms, -250);
+        assert_eq!(times_and_texts(&lyrics), vec![(1250, "a")]);
+    }
+
+    #[test]
+    fn bad_offset_is_ignored() {
+        let lyrics = parse_lrc("[offset:soon]\n[00:01.00]a");
+        assert_eq!(lyrics.metadata.offset_ms, 0);
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "a")]);
+    }
+
+    #[test]
+    fn keeps_empty_lines() {
+        let lyrics = parse_lrc("[00:05.00]first\n[00:06.00]\n[00:07.00]last");
+        assert_eq!(times_and_texts(&lyrics), vec![(5000, "first"), (6000, ""), (7000, "last")]);
+    }
+
+    #[test]
+    fn groups_same_timestamp_lines_as_translation() {
+        let lyrics = parse_lrc("[00:05.00]hello\n[00:05.00]你好\n[00:06.00]world\n[00:06.00]世界\n[00:06.00]third\n");
+        let pairs: Vec<(&str, Option<&str>)> = lyrics.lines.iter().map(|l| (l.text.as_str(), l.translation.as_deref())).collect();
+        // A third line with the same time stays a line of its own, in file order
+        assert_eq!(pairs, vec![("hello", Some("你好")), ("world", Some("世界")), ("third", None)]);
+    }
+
+    #[test]
+    fn empty_lines_are_not_translations() {
+        let lyrics = parse_lrc("[00:05.00]\n[00:05.00]text");
+        assert_eq!(times_and_texts(&lyrics), vec![(5000, ""), (5000, "text")]);
+        assert!(lyrics.lines.iter().all(|l| l.translation.is_none()));
+    }
+
+    #[test]
+    fn merges_translation_file() {
+        let mut lyrics = parse_lrc("[00:01.00]one\n[00:02.00]\n[00:03.00]three\n[00:04.00]four");
+        let translation = parse_lrc("[00:01.05]一\n[00:02.00]空\n[00:03.00]三\n[00:09.00]九");
+        assert_eq!(lyrics.merge_translation(&translation), 2);
+        let translations: Vec<Option<&str>> = lyrics.lines.iter().map(|l| l.translation.as_deref()).collect();
+        assert_eq!(translations, vec![Some("一"), None, Some("三"), None]);
+    }
+
+    #[test]
+    fn handles_bom_and_crlf() {
+        let lyrics = parse_lrc("\u{FEFF}[ti:T]\r\n[00:01.00]a\r\n");
+        assert_eq!(lyrics.metadata.title.as_deref(), Some("T"));
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "a")]);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let lyrics = parse_lrc(
+            "plain text\n[00:01.00\n[aa:bb]x\n[00:61.00]bad seconds\n[-1:00.00]negative\n[00:02.xx]bad fraction\n[]\n[:]\n\n[00:03.00]ok",
+        );
+        assert_eq!(times_and_texts(&lyrics), vec![(3000, "ok")]);
+        assert_eq!(lyrics.metadata, LrcMetadata::default());
+    }
+
+    #[test]
+    fn brackets_after_timestamp_stay_in_text() {
+        let lyrics = parse_lrc("[00:01.00][Chorus] la la [x]");
+        assert_eq!(times_and_texts(&lyrics), vec![(1000, "[Chorus] la la [x]")]);
+    }
+
+    #[test]
+    fn parses_word_timings() {
+        let lyrics = parse_lrc("[00:01.00]<00:01.00>Hello <00:01.50>world<00:02.00>");
+        let line = &lyrics.lines[0];
+        assert_eq!(line.text, "Hello world");
+        let words: Vec<(u64, &str)> = line.words.iter().map(|w| (w.time.as_millis() as u64, w.text.as_str())).collect();
+        assert_eq!(words, vec![(1000, "Hello "), (1500, "world"), (2000, "")]);
+    }
+
+    #[test]
+    fn word_timings_with_lead_in_and_literal_brackets() {
+        let lyrics = parse_lrc("[00:01.00]Oh <00:01.50>a<b <00:02.00>c");
+        let line = &lyrics.lines[0];
+        assert_eq!(line.text, "Oh a<b c");
+        let words: Vec<(u64, &str)> = line.words.iter().map(|w| (w.time.as_millis() as u64, w.text.as_str())).collect();
+        assert_eq!(words, vec![(1000, "Oh "), (1500, "a<b "), (2000, "c")]);
+    }
+
+    #[test]
+    fn word_timings_follow_repeats_and_offset() {
+        let lyrics = parse_lrc("[offset:500]\n[00:10.00][01:00.00]<00:10.00>a<00:11.00>b");
+        let starts: Vec<Vec<u64>> = lyrics.lines.iter()
+            .map(|l| l.words.iter().map(|w| w.time.as_millis() as u64).collect())
+            .collect();
+        assert_eq!(starts, vec![vec![9500, 10500], vec![59500, 60500]]);
+    }
+
+    #[test]
+    fn plain_lines_have_no_words() {
+        let lyrics = parse_lrc("[00:01.00]a <not a time> b");
+        assert_eq!(lyrics.lines[0].text, "a <not a time> b");
+        assert!(lyrics.lines[0].words.is_empty());
+        assert_eq!(lyrics.lines[0].highlight_chars(ms(1500), None), None);
+    }
+
+    #[test]
+    fn highlight_progress() {
+        let lyrics = parse_lrc("[00:01.00]<00:01.00>ab<00:02.00>cd<00:03.00>");
+        let line = &lyrics.lines[0];
+        assert_eq!(line.highlight_chars(ms(500), None), Some(0.0));
+        assert_eq!(line.highlight_chars(ms(1500), None), Some(1.0));
+        assert_eq!(line.highlight_chars(ms(2000), None), Some(2.0));
+        assert_eq!(line.highlight_chars(ms(2500), None), Some(3.0));
+        assert_eq!(line.highlight_chars(ms(9000), None), Some(4.0));
+
+        // Without an end tag the last word runs until the next line
+        let lyrics = parse_lrc("[00:01.00]<00:01.00>ab<00:02.00>cd");
+        assert_eq!(lyrics.lines[0].highlight_chars(ms(3000), Some(ms(4000))), Some(3.0));
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(ms(0)), "00:00.00");
+        assert_eq!(format_timestamp(ms(61_239)), "01:01.23");
+        assert_eq!(format_timestamp(Duration::from_secs(6000)), "100:00.00");
+    }
+
+    #[test]
+    fn to_lrc_round_trips() {
+        let text = "[ti:Song]\n[ar:Artist]\n[length:03:25]\n[00:01.00]<00:01.00>Hello <00:01.50>world<00:02.00>\n[00:03.00]hi\n[00:03.00]你好\n[00:04.50]\n";
+        let lyrics = parse_lrc(text);
+        assert_eq!(lyrics.to_lrc(), text);
+        assert_eq!(parse_lrc(&lyrics.to_lrc()), lyrics);
+    }
+
+    #[test]
+    fn to_lrc_bakes_in_offset() {
+        let lyrics = parse_lrc("[offset:500]\n[00:01.00]a");
+        assert_eq!(lyrics.to_lrc(), "[00:00.50]a\n");
+    }
+
+    #[test]
+    fn line_at_position() {
+        let lyrics = parse_lrc("[00:01.00]a\n[00:02.00]b");
+        assert_eq!(lyrics.line_at(ms(500)), None);
+        assert_eq!(lyrics.line_at(ms(1000)), Some(0));
+        assert_eq!(lyrics.line_at(ms(1999)), Some(0));
+        assert_eq!(lyrics.line_at(ms(5000)), Some(1));
+    }
+}
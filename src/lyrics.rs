@@ -0,0 +1,173 @@
+// --- Lyrics Sources ---
+
+use super::lrc::{parse_lrc, Lyrics};
+use lofty::id3::v2::{Frame, SynchronizedTextFrame, TimestampFormat};
+use lofty::prelude::{ItemKey, TaggedFileExt};
+use lofty::probe::Probe;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LyricsSource {
+    // <file>.lrc next to the audio file
+    Sibling,
+    // USLT / SYLT (ID3v2), LYRICS (Vorbis, APE), ©lyr (MP4)
+    Embedded,
+    // The configured lyrics folders, trying several file name variants
+    Directories,
+}
+
+pub const DEFAULT_LYRICS_SOURCES: [LyricsSource; 3] = [LyricsSource::Sibling, LyricsSource::Embedded, LyricsSource::Directories];
+
+impl LyricsSource {
+    pub fn as_str(&self, lang: super::Language) -> &'static str {
+        lang.tr(match self {
+            LyricsSource::Sibling => "lyrics_source.sibling",
+            LyricsSource::Embedded => "lyrics_source.embedded",
+            LyricsSource::Directories => "lyrics_source.directories",
+        })
+    }
+}
+
+/// Finds lyrics for a track, trying the sources in order. Timed (LRC) lyrics
+/// win over plain text from an earlier source, since only they can be shown
+/// in sync; plain text is returned if nothing better turns up.
+pub fn resolve(path: &Path, sources: &[LyricsSource], dirs: &[PathBuf]) -> Option<String> {
+    let mut plain = None;
+    for source in sources {
+        let found = match source {
+            LyricsSource::Sibling => read_text(&path.with_extension("lrc")),
+            LyricsSource::Embedded => read_embedded(path),
+            LyricsSource::Directories => find_in_dirs(path, dirs, "lrc"),
+        };
+        match found {
+            Some(text) if is_timed(&text) => return Some(text),
+            Some(text) if plain.is_none() && !text.trim().is_empty() => plain = Some(text),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Resolves and parses the lyrics for a track, merging in a separate
+/// translation file if there is one. Empty if nothing was found.
+pub fn load(path: &Path, sources: &[LyricsSource], dirs: &[PathBuf]) -> Lyrics {
+    let Some(content) = resolve(path, sources, dirs) else { return Lyrics::default() };
+    parse_with_translation(&content, path, dirs)
+}
+
+/// Parses lyrics for a track and merges in its translation file, if any.
+pub fn parse_with_translation(content: &str, path: &Path, dirs: &[PathBuf]) -> Lyrics {
+    let mut lyrics = parse_lrc(content);
+    if let Some(translation) = resolve_translation(path, dirs) {
+        lyrics.merge_translation(&parse_lrc(&translation));
+    }
+    lyrics
+}
+
+/// Finds a separate translation file (`<name>.trans.lrc`) next to the track or
+/// in the lyrics folders.
+pub fn resolve_translation(path: &Path, dirs: &[PathBuf]) -> Option<String> {
+    read_text(&path.with_extension(TRANSLATION_EXTENSION)).or_else(|| find_in_dirs(path, dirs, TRANSLATION_EXTENSION))
+}
+
+const TRANSLATION_EXTENSION: &str = "trans.lrc";
+
+fn is_timed(text: &str) -> bool {
+    !parse_lrc(text).is_empty()
+}
+
+// Strips a UTF-8 BOM; invalid bytes are replaced rather than failing the load
+fn read_text(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    Some(String::from_utf8_lossy(bytes).to_string())
+}
+
+fn read_embedded(path: &Path) -> Option<String> {
+    // SYLT isn't exposed through lofty's generic tag, so it's read separately
+    if let Some(synced) = read_sylt(path) {
+        return Some(synced);
+    }
+    let tagged_file = Probe::open(path).and_then(|p| p.read()).ok()?;
+    tagged_file.tags().iter()
+        .find_map(|tag| tag.get_string(&ItemKey::Lyrics).filter(|text| !text.trim().is_empty()))
+        .map(str::to_string)
+}
+
+// --- Lyrics folders ---
+
+fn find_in_dirs(path: &Path, dirs: &[PathBuf], extension: &str) -> Option<String> {
+    let variants = name_variants(path);
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+        let files: Vec<(String, PathBuf)> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| (e.file_name().to_string_lossy().to_lowercase(), e.path()))
+            .filter(|(name, _)| name.ends_with(extension))
+            .collect();
+        // Variants are ordered best first, so check them before the next file
+        for variant in &variants {
+            let wanted = format!("{}.{}", variant, extension);
+            if let Some((_, lrc_path)) = files.iter().find(|(name, _)| *name == wanted) {
+                return read_text(lrc_path);
+            }
+        }
+    }
+    None
+}
+
+/// Lower-case file stems to look for: the audio file's own stem, then
+/// "artist - title", "title - artist" and "title" from its tags, then the stem
+/// without a leading track number ("01 - Song", "01. Song").
+fn name_variants(path: &Path) -> Vec<String> {
+    let mut variants = Vec::new();
+    let mut push = |name: String| {
+        let name = name.trim().to_lowercase();
+        if !name.is_empty() && !variants.contains(&name) {
+            variants.push(name);
+        }
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    push(stem.clone());
+
+    let meta = super::read_track_meta(path);
+    if !meta.title.is_empty() {
+        if !meta.artist.is_empty() {
+            push(format!("{} - {}", meta.artist, meta.title));
+            push(format!("{} - {}", meta.title, meta.artist));
+        }
+        push(meta.title.clone());
+    }
+
+    let without_number = stem.trim_start_matches(|c: char| c.is_ascii_digit());
+    if without_number.len() != stem.len() {
+        push(without_number.trim_start_matches([' ', '.', '-', '_']).to_string());
+    }
+    variants
+}
+
+// --- ID3v2 SYLT ---
+
+/// Turns the first SYLT (synchronised lyrics) frame of an ID3v2 tag into LRC
+/// text. lofty undoes unsynchronisation and upgrades ID3v2.2 `SLT` frames, but
+/// leaves SYLT unparsed. Only millisecond timestamps are supported.
+fn read_sylt(path: &Path) -> Option<String> {
+    let tag = super::tags::read_id3v2(path).ok()??;
+    tag.into_iter()
+        .filter(|frame| frame.id().as_str() == "SYLT")
+        .filter_map(|frame| match &frame {
+            Frame::Binary(binary) => SynchronizedTextFrame::parse(&binary.data, frame.flags()).ok(),
+            _ => None,
+        })
+        // MPEG frame based timestamps would need the frame rate
+        .find(|sylt| sylt.timestamp_format == TimestampFormat::MS && !sylt.content.is_empty())
+        .map(|sylt| {
+            sylt.content.iter()
+                .map(|(ms, text)| {
+                    let (ms, text) = (*ms as u64, text.trim_start_matches('\n'));
+                    format!("[{:02}:{:02}.{:02}]{}\n", ms / 60000, ms / 1000 % 60, ms % 1000 / 10, text)
+                })
+                .collect()
+        })
+}
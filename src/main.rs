@@ -1,27 +1,56 @@
 #![windows_subsystem = "windows"]
 
 use axum::{
-    extract::State,
+    extract::{Query, State},
     routing::{get, post},
     Json, Router,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use eframe::egui;
-use lofty::prelude::AudioFile;
+use lofty::prelude::{Accessor, AudioFile, TaggedFileExt};
+use lofty::config::WriteOptions;
 use lofty::probe::Probe;
+use lofty::id3::v2::{Frame, FrameId, PopularimeterFrame};
+use lofty::tag::{ItemKey, Tag, TagExt};
 use rodio::{Decoder, OutputStream, Sink, Source};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::File;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::future::IntoFuture;
-use std::io::{BufReader, Cursor};
-use std::path::PathBuf;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 
+mod fonts;
+mod i18n;
+mod lrc;
+mod lyrics;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod paths;
+mod playlist_formats;
+mod shortcuts;
+mod smart_playlist;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+mod storage;
+mod tags;
+mod theme;
+use playlist_formats::{PlaylistFormat, PLAYLIST_EXTENSIONS};
+use tags::{CoverChange, CoverInfo, TagChanges, TagFields};
+use theme::{color, Theme, ThemeBase};
+use fonts::FontChoice;
+use i18n::Language;
+use lrc::{format_timestamp, parse_lrc, LrcMetadata, LyricLine, Lyrics};
+use lyrics::{LyricsSource, DEFAULT_LYRICS_SOURCES};
+use shortcuts::{Action, Bindings, MediaRequest, SEEK_STEP, VOLUME_STEP};
+use smart_playlist::{SmartField, SmartOp, SmartPlaylist, SmartRule, NUMERIC_OPS, SMART_FIELDS, SMART_SORTS, TEXT_OPS};
+
 // --- Audio Engine ---
 
 enum AudioCommand {
@@ -234,55 +263,753 @@ fn start_audio_thread() -> (Sender<AudioCommand>, Receiver<AudioStatus>) {
 
 // --- Persistence ---
 
-fn get_config_path(filename: &str) -> PathBuf {
-    if let Ok(mut exe_path) = std::env::current_exe() {
-        exe_path.pop();
-        exe_path.push(filename);
-        return exe_path;
-    }
-    PathBuf::from(filename)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+struct PlaylistItem {
+    path: PathBuf,
+    name: String,
+}
+
+// Tag data cached per file so search doesn't have to hit the disk
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+struct TrackMeta {
+    title: String,
+    artist: String,
+    album: String,
+    genre: String,
+    duration_secs: u64,
+    // 0-5 stars
+    rating: u8,
+    favourite: bool,
+    play_count: u32,
+    // Unix timestamps, 0 if unknown
+    last_played: u64,
+    added: u64,
 }
 
+// One listen, logged when the track ends or is changed
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-struct PlaylistItem {
+struct HistoryEntry {
     path: PathBuf,
-    name: String,
+    // Unix timestamp of when playback started
+    timestamp: u64,
+    listened_secs: u64,
+    skipped: bool,
+}
+
+// Oldest entries are dropped beyond this
+const HISTORY_LIMIT: usize = 10_000;
+const HISTORY_FILE: &str = "history.jsonl";
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_track_meta(path: &Path) -> TrackMeta {
+    let mut meta = TrackMeta::default();
+    if let Ok(tagged_file) = Probe::open(path).and_then(|p| p.read()) {
+        meta.duration_secs = tagged_file.properties().duration().as_secs();
+        if let Some(tag) = tagged_file.primary_tag().or_else(|| tagged_file.first_tag()) {
+            meta.title = tag.title().map(|s| s.to_string()).unwrap_or_default();
+            meta.artist = tag.artist().map(|s| s.to_string()).unwrap_or_default();
+            meta.album = tag.album().map(|s| s.to_string()).unwrap_or_default();
+            meta.genre = tag.genre().map(|s| s.to_string()).unwrap_or_default();
+        }
+    }
+    meta
+}
+
+// POPM rating byte for 0-5 stars, using the scale Windows Media Player reads
+const POPM_RATINGS: [u8; 6] = [0, 1, 64, 128, 196, 255];
+const POPM_EMAIL: &str = "Windows Media Player 9 Series";
+
+/// Writes a 0-5 star rating into the file's tag: POPM for ID3v2 (MP3 and
+/// WAV), FMPS_RATING (0.0-1.0) for Vorbis comments, APE and the like.
+fn write_rating_tag(path: &Path, rating: u8) -> Result<(), String> {
+    let rating = rating.min(5);
+    if let Some(mut tag) = tags::read_id3v2(path).map_err(|e| e.to_string())? {
+        // Other players keep their own POPM frames; only ours is replaced
+        let frames: Vec<Frame> = tag.remove(&FrameId::Valid(Cow::Borrowed("POPM"))).collect();
+        let mut counter = 0;
+        for frame in frames {
+            match frame {
+                Frame::Popularimeter(popm) if popm.email == POPM_EMAIL => counter = popm.counter,
+                frame => {
+                    tag.insert(frame);
+                }
+            }
+        }
+        if rating > 0 {
+            let popm = PopularimeterFrame::new(POPM_EMAIL.into(), POPM_RATINGS[rating as usize], counter);
+            tag.insert(Frame::Popularimeter(popm));
+        }
+        return tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string());
+    }
+
+    let mut tagged_file = Probe::open(path).and_then(|probe| probe.read()).map_err(|e| e.to_string())?;
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else { return Ok(()) };
+    let key = ItemKey::Unknown("FMPS_RATING".to_string());
+    tag.remove_key(&key);
+    if rating > 0 && !tag.insert_text(key, format!("{:.1}", rating as f32 / 5.0)) {
+        return Err(format!("{:?} tags can't hold a rating", tag.tag_type()));
+    }
+    tag.save_to_path(path, WriteOptions::default()).map_err(|e| e.to_string())
+}
+
+/// Writes tag changes to files and re-reads them into the library, returning
+/// an error message per failed file. The lock is only taken for the merge.
+fn write_tags_and_reindex(data: &Mutex<PlaylistsManager>, paths: &[PathBuf], changes: &TagChanges) -> Vec<String> {
+    let errors = paths.iter()
+        .filter_map(|path| tags::write_tags(path, changes).err().map(|e| format!("{:?}: {}", path, e)))
+        .collect();
+    let reread = paths.iter().map(|path| (path.clone(), read_track_meta(path))).collect();
+    let mut data = data.lock().unwrap();
+    data.reindex_tracks(reread);
+    data.save();
+    errors
+}
+
+/// Reads the tags of the paths that have no library entry yet, without
+/// holding the lock while doing so.
+fn read_unindexed_meta(data: &Mutex<PlaylistsManager>, paths: &[PathBuf]) -> HashMap<PathBuf, TrackMeta> {
+    let unindexed: Vec<PathBuf> = {
+        let data = data.lock().unwrap();
+        paths.iter().filter(|path| !data.library.contains_key(*path)).cloned().collect()
+    };
+    unindexed.into_iter().map(|path| {
+        let meta = read_track_meta(&path);
+        (path, meta)
+    }).collect()
+}
+
+/// Writes ratings to file tags in the background, if `enabled` (the
+/// "write ratings to file tags" setting).
+fn spawn_rating_tag_writes(data: &PlaylistsManager, paths: Vec<PathBuf>, enabled: bool) {
+    if paths.is_empty() || !enabled {
+        return;
+    }
+    let ratings: Vec<(PathBuf, u8)> = paths.into_iter()
+        .map(|path| {
+            let rating = data.library.get(&path).map_or(0, |meta| meta.rating);
+            (path, rating)
+        })
+        .collect();
+    thread::spawn(move || {
+        for (path, rating) in ratings {
+            if let Err(e) = write_rating_tag(&path, rating) {
+                eprintln!("Failed to write rating to {:?}: {}", path, e);
+            }
+        }
+    });
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+enum SortColumn {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Rating,
+    PlayCount,
+    LastPlayed,
+    Path,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+struct PlaylistSort {
+    column: SortColumn,
+    descending: bool,
+}
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "ogg", "oga"];
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Expands folders recursively and keeps only files with an audio extension.
+/// Each folder is walked once, so a symlink pointing back up the tree can't
+/// send this round in circles.
+fn collect_audio_files(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    fn walk(paths: Vec<PathBuf>, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) {
+        for path in paths {
+            if path.is_dir() {
+                let Ok(real_path) = path.canonicalize() else { continue };
+                if !visited.insert(real_path) {
+                    continue;
+                }
+                if let Ok(entries) = std::fs::read_dir(&path) {
+                    let mut children: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+                    children.sort();
+                    walk(children, visited, files);
+                }
+            } else if is_audio_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(paths, &mut HashSet::new(), &mut files);
+    files
+}
+
+// Appends " (n)" until the name is unique within the list
+fn unique_item_name(list: &[PlaylistItem], name: &str) -> String {
+    let mut final_name = name.to_string();
+    let mut count = 1;
+    while list.iter().any(|item| item.name == final_name) {
+        final_name = format!("{} ({})", name, count);
+        count += 1;
+    }
+    final_name
+}
+
+// Where the selected items currently are in a list, in list order
+fn selected_indices(list: &[PlaylistItem], selected: &HashSet<PlaylistItem>) -> Vec<usize> {
+    list.iter().enumerate()
+        .filter(|(_, item)| selected.contains(*item))
+        .map(|(index, _)| index)
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct PlaylistsManager {
+    #[serde(default)]
+    version: u32,
     current_name: String,
     lists: HashMap<String, Vec<PlaylistItem>>,
+    #[serde(default)]
+    library: HashMap<PathBuf, TrackMeta>,
+    // Lists that stay sorted when items are added
+    #[serde(default)]
+    sort_orders: HashMap<String, PlaylistSort>,
+    // Tracks to play before continuing with the current list
+    #[serde(default)]
+    queue: Vec<PathBuf>,
+    // Rule-based playlists. Their computed contents live in `lists` under the same name.
+    #[serde(default)]
+    smart: HashMap<String, SmartPlaylist>,
+    // Listening history, oldest first. Kept in history.jsonl (or the database)
+    // rather than here, so logging a listen appends instead of rewriting it all.
+    #[serde(default, skip_serializing)]
+    history: Vec<HistoryEntry>,
+    // Tracks already in a playlist but not indexed when the file was loaded.
+    // When they were added isn't known, so the indexer goes by the file date.
+    #[serde(skip)]
+    added_unknown: HashSet<PathBuf>,
 }
 
-impl PlaylistsManager {
-    fn load() -> Self {
-        let path = get_config_path("playlists.json");
-        if let Ok(file) = File::open(&path) {
-            if let Ok(data) = serde_json::from_reader(BufReader::new(file)) {
-                return data;
-            }
-        }
-        // Fallback or migration could go here, but for now we start fresh if schema mismatches
+impl Default for PlaylistsManager {
+    fn default() -> Self {
         let mut lists = HashMap::new();
         lists.insert("Default List".to_string(), Vec::new());
         lists.insert("默认列表".to_string(), Vec::new());
         Self {
+            version: PLAYLISTS_VERSION,
             current_name: "Default List".to_string(),
             lists,
+            library: HashMap::new(),
+            sort_orders: HashMap::new(),
+            queue: Vec::new(),
+            smart: HashMap::new(),
+            history: Vec::new(),
+            added_unknown: HashSet::new(),
+        }
+    }
+}
+
+// Automatic smart playlist of tracks marked as favourite
+const FAVOURITES_PLAYLIST: &str = "Favourites";
+
+// Schema versions of the two files; bump and extend the migrate fn on change
+const PLAYLISTS_VERSION: u32 = 3;
+const CONFIG_VERSION: u32 = 3;
+
+fn migrate_playlists(mut value: serde_json::Value, from: u32) -> serde_json::Value {
+    let Some(obj) = value.as_object_mut() else { return value };
+    if from < 2 {
+        // v1 only had current_name and lists; the library, sort orders,
+        // queue and smart playlists were added later
+        for key in ["library", "sort_orders", "smart"] {
+            obj.entry(key).or_insert_with(|| serde_json::json!({}));
+        }
+        obj.entry("queue").or_insert_with(|| serde_json::json!([]));
+    }
+    if from < 3 {
+        // v3 added the listening history
+        obj.entry("history").or_insert_with(|| serde_json::json!([]));
+    }
+    obj.insert("version".to_string(), PLAYLISTS_VERSION.into());
+    value
+}
+
+fn migrate_config(mut value: serde_json::Value, from: u32) -> serde_json::Value {
+    let Some(obj) = value.as_object_mut() else { return value };
+    if from < 3 {
+        // v3 replaced the single ui_bg_color with a theme
+        if let Some(bg) = obj.remove("ui_bg_color").and_then(|bg| serde_json::from_value::<[u8; 4]>(bg).ok()) {
+            let mut theme = Theme::dark();
+            if bg != theme.background {
+                theme.name = "Custom".to_string();
+                theme.background = bg;
+            }
+            obj.insert("theme".to_string(), serde_json::to_value(theme).unwrap_or_default());
+        }
+    }
+    obj.insert("version".to_string(), CONFIG_VERSION.into());
+    value
+}
+
+impl PlaylistsManager {
+    /// Loads the playlists, along with a message to show if the file was
+    /// unreadable and had to be restored from a backup or reset.
+    fn load() -> (Self, Option<String>) {
+        #[cfg(feature = "sqlite")]
+        let db_error = match sqlite_store::load_db() {
+            Ok(Some(mut data)) => {
+                data.added_unknown = data.unindexed_paths().into_iter().collect();
+                return (data, None);
+            }
+            // Fresh database: import playlists.json into it below
+            Ok(None) => None,
+            Err(e) => Some(format!("Failed to open library database, using playlists.json instead: {}", e)),
+        };
+
+        let path = paths::data_file("playlists.json");
+        let (data, error) = storage::load_versioned(&path, PLAYLISTS_VERSION, migrate_playlists);
+        let mut data: Self = data.unwrap_or_default();
+        // Files from before history.jsonl carry the history themselves
+        let history_path = paths::data_file(HISTORY_FILE);
+        let legacy_history = !history_path.exists() && !data.history.is_empty();
+        if !legacy_history {
+            data.history = Self::load_history_file(&history_path);
+        }
+        let trimmed = data.history.len() > HISTORY_LIMIT;
+        if trimmed {
+            let excess = data.history.len() - HISTORY_LIMIT;
+            data.history.drain(..excess);
+        }
+        let history_changed = legacy_history || trimmed;
+        data.added_unknown = data.unindexed_paths().into_iter().collect();
+
+        #[cfg(feature = "sqlite")]
+        let history_changed = history_changed || db_error.is_none();
+        #[cfg(feature = "sqlite")]
+        let error = {
+            if db_error.is_none() {
+                data.save();
+            }
+            let errors: Vec<String> = [db_error, error].into_iter().flatten().collect();
+            Some(errors.join("\n\n")).filter(|e| !e.is_empty())
+        };
+        if history_changed {
+            data.save_history();
+        }
+        (data, error)
+    }
+
+    fn is_smart(&self, name: &str) -> bool {
+        self.smart.contains_key(name)
+    }
+
+    // Whether the track is in the library or a playlist. The HTTP API only
+    // modifies files the user has added, not any path it is sent.
+    fn has_track(&self, path: &Path) -> bool {
+        self.library.contains_key(path) || self.lists.values().flatten().any(|item| item.path == path)
+    }
+
+    // Lists the app maintains itself, which can't be renamed, deleted or edited
+    fn is_builtin(&self, name: &str) -> bool {
+        name == FAVOURITES_PLAYLIST && self.is_smart(name)
+    }
+
+    /// Sets the rating and/or favourite flag of tracks, adding them to the
+    /// library with their tags from `fresh` if needed (see
+    /// `read_unindexed_meta`). Returns the paths whose rating changed.
+    fn rate_tracks(&mut self, paths: &[PathBuf], mut fresh: HashMap<PathBuf, TrackMeta>, rating: Option<u8>, favourite: Option<bool>) -> Vec<PathBuf> {
+        let mut rating_changed = Vec::new();
+        for path in paths {
+            let meta = self.library.entry(path.clone()).or_insert_with(|| {
+                TrackMeta { added: unix_now(), ..fresh.remove(path).unwrap_or_default() }
+            });
+            if let Some(rating) = rating {
+                let rating = rating.min(5);
+                if meta.rating != rating {
+                    meta.rating = rating;
+                    rating_changed.push(path.clone());
+                }
+            }
+            if let Some(favourite) = favourite {
+                meta.favourite = favourite;
+            }
+        }
+        self.refresh_smart_playlists();
+        rating_changed
+    }
+
+    /// Recomputes the contents of every smart playlist from the library.
+    fn refresh_smart_playlists(&mut self) {
+        // The favourites list always exists, unless a regular list already has its name
+        if !self.lists.contains_key(FAVOURITES_PLAYLIST) || self.is_smart(FAVOURITES_PLAYLIST) {
+            self.smart.entry(FAVOURITES_PLAYLIST.to_string()).or_insert_with(SmartPlaylist::favourites);
+        }
+        let now = unix_now();
+        for (name, smart) in &self.smart {
+            let mut matching: Vec<(&PathBuf, &TrackMeta)> = self.library.iter()
+                .filter(|(_, meta)| smart.matches(meta, now))
+                .collect();
+            // Library order is random; start from a stable one so ties don't shuffle
+            matching.sort_by(|a, b| a.0.cmp(b.0));
+            let mut items: Vec<PlaylistItem> = Vec::new();
+            for path in smart.arrange(matching) {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let name = unique_item_name(&items, &file_name);
+                items.push(PlaylistItem { path: path.clone(), name });
+            }
+            self.lists.insert(name.clone(), items);
+        }
+    }
+
+    /// Removes the given indices from a list, ignoring any that are out of bounds.
+    fn remove_items(&mut self, name: &str, indices: &[usize]) -> usize {
+        if self.is_smart(name) {
+            return 0;
+        }
+        let Some(list) = self.lists.get_mut(name) else { return 0 };
+        let mut indices: Vec<usize> = indices.iter().copied().filter(|&i| i < list.len()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        for &index in indices.iter().rev() {
+            list.remove(index);
+        }
+        indices.len()
+    }
+
+    /// Copies items to the top of another list. Files already in the target are skipped.
+    fn copy_items(&mut self, from: &str, indices: &[usize], to: &str) -> usize {
+        if self.is_smart(to) {
+            return 0;
+        }
+        let Some(source) = self.lists.get(from) else { return 0 };
+        let items: Vec<PlaylistItem> = indices.iter().filter_map(|&i| source.get(i).cloned()).collect();
+        let target = self.lists.entry(to.to_string()).or_default();
+        let mut added = 0;
+        for item in items.into_iter().rev() {
+            if target.iter().any(|existing| existing.path == item.path) {
+                continue;
+            }
+            let name = unique_item_name(target, &item.name);
+            target.insert(0, PlaylistItem { path: item.path, name });
+            added += 1;
+        }
+        self.apply_sort(to);
+        added
+    }
+
+    fn unique_playlist_name(&self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut count = 1;
+        while self.lists.contains_key(&name) {
+            name = format!("{} ({})", base, count);
+            count += 1;
+        }
+        name
+    }
+
+    /// Adds the tracks of an imported playlist. Without a target name a new
+    /// playlist named `new_name` is created; an existing target gets the tracks appended.
+    fn import_playlist(&mut self, paths: Vec<PathBuf>, target: Option<String>, new_name: &str) -> std::io::Result<(String, usize)> {
+        if target.as_deref().is_some_and(|name| self.is_smart(name)) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Smart playlists are read-only"));
+        }
+        let name = target.unwrap_or_else(|| self.unique_playlist_name(new_name));
+        let list = self.lists.entry(name.clone()).or_default();
+        let mut added = 0;
+        for path in paths {
+            if list.iter().any(|item| item.path == path) {
+                continue;
+            }
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let item_name = unique_item_name(list, &file_name);
+            list.push(PlaylistItem { path, name: item_name });
+            added += 1;
+        }
+        self.apply_sort(&name);
+        Ok((name, added))
+    }
+
+    /// A playlist's items with their library metadata, for exporting.
+    fn export_items(&self, name: &str) -> Option<Vec<(PlaylistItem, TrackMeta)>> {
+        let list = self.lists.get(name)?;
+        Some(list.iter()
+            .map(|item| (item.clone(), self.library.get(&item.path).cloned().unwrap_or_default()))
+            .collect())
+    }
+
+    /// Exports a playlist to a file picked in the UI; the format follows the
+    /// file extension unless given. Not for the HTTP API, which must not write
+    /// to paths a caller picks.
+    fn export_playlist_file(&self, name: &str, path: &Path, format: Option<PlaylistFormat>, relative: bool) -> std::io::Result<usize> {
+        let format = format.or_else(|| PlaylistFormat::from_path(path))
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unsupported playlist format"))?;
+        let items = self.export_items(name)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Playlist not found"))?;
+        playlist_formats::export_playlist(path, name, &items, format, relative)?;
+        Ok(items.len())
+    }
+
+    fn queue_items(&mut self, name: &str, indices: &[usize]) -> usize {
+        let Some(list) = self.lists.get(name) else { return 0 };
+        let paths: Vec<PathBuf> = indices.iter().filter_map(|&i| list.get(i).map(|item| item.path.clone())).collect();
+        let count = paths.len();
+        self.queue.extend(paths);
+        count
+    }
+
+    fn sort_list(&mut self, name: &str, sort: PlaylistSort) {
+        let library = &self.library;
+        if let Some(list) = self.lists.get_mut(name) {
+            list.sort_by_cached_key(|item| {
+                let meta = library.get(&item.path).cloned().unwrap_or_default();
+                match sort.column {
+                    SortColumn::Title if meta.title.is_empty() => (item.name.to_lowercase(), 0),
+                    SortColumn::Title => (meta.title.to_lowercase(), 0),
+                    SortColumn::Artist => (meta.artist.to_lowercase(), 0),
+                    SortColumn::Album => (meta.album.to_lowercase(), 0),
+                    SortColumn::Duration => (String::new(), meta.duration_secs),
+                    // Favourites first among equal ratings
+                    SortColumn::Rating => (String::new(), meta.rating as u64 * 2 + meta.favourite as u64),
+                    SortColumn::PlayCount => (String::new(), meta.play_count as u64),
+                    SortColumn::LastPlayed => (String::new(), meta.last_played),
+                    SortColumn::Path => (item.path.to_string_lossy().to_lowercase(), 0),
+                }
+            });
+            if sort.descending {
+                list.reverse();
+            }
+        }
+    }
+
+    /// Re-applies the persistent sort of a list, if it has one.
+    fn apply_sort(&mut self, name: &str) {
+        if let Some(sort) = self.sort_orders.get(name).copied() {
+            self.sort_list(name, sort);
+        }
+    }
+
+    /// Moves an item so that it ends up in front of what is currently at `insert_at`.
+    fn move_item(&mut self, name: &str, from: usize, insert_at: usize) {
+        if let Some(list) = self.lists.get_mut(name) {
+            if from < list.len() && insert_at <= list.len() {
+                let item = list.remove(from);
+                let to = if insert_at > from { insert_at - 1 } else { insert_at };
+                list.insert(to, item);
+            }
+        }
+    }
+
+    /// Logs a listen and, unless it was skipped, counts it as a play. `meta`
+    /// is the track's tags if it isn't in the library yet, read beforehand so
+    /// the disk isn't hit while the lock is held.
+    fn record_play(&mut self, entry: HistoryEntry, meta: Option<TrackMeta>) {
+        let meta = self.library.entry(entry.path.clone()).or_insert_with(|| {
+            let mut meta = meta.unwrap_or_else(|| read_track_meta(&entry.path));
+            meta.added = unix_now();
+            meta
+        });
+        if !entry.skipped {
+            meta.play_count += 1;
+            meta.last_played = entry.timestamp;
+        }
+        self.history.push(entry);
+        if self.history.len() > HISTORY_LIMIT {
+            let excess = self.history.len() - HISTORY_LIMIT;
+            self.history.drain(..excess);
+        }
+        self.append_history();
+        // Play counts feed "most played" style rules
+        if !self.smart.is_empty() {
+            self.refresh_smart_playlists();
+        }
+    }
+
+    /// Takes in re-read tags of edited files, keeping ratings and play statistics.
+    fn reindex_tracks(&mut self, reread: Vec<(PathBuf, TrackMeta)>) {
+        for (path, fresh) in reread {
+            let meta = self.library.entry(path).or_insert_with(|| TrackMeta { added: unix_now(), ..Default::default() });
+            meta.title = fresh.title;
+            meta.artist = fresh.artist;
+            meta.album = fresh.album;
+            meta.genre = fresh.genre;
+            meta.duration_secs = fresh.duration_secs;
+        }
+        self.refresh_smart_playlists();
+        for name in self.sort_orders.keys().cloned().collect::<Vec<_>>() {
+            self.apply_sort(&name);
+        }
+    }
+
+    /// Paths referenced by any playlist that have no library entry yet.
+    fn unindexed_paths(&self) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
+        let mut missing = Vec::new();
+        for list in self.lists.values() {
+            for item in list {
+                if !self.library.contains_key(&item.path) && seen.insert(&item.path) {
+                    missing.push(item.path.clone());
+                }
+            }
+        }
+        missing
+    }
+
+    // Skips lines that don't parse, such as one cut short by a crash
+    fn load_history_file(path: &Path) -> Vec<HistoryEntry> {
+        let Ok(text) = std::fs::read_to_string(path) else { return Vec::new() };
+        text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+    }
+
+    // Stores the newest history entry
+    fn append_history(&self) {
+        let Some(entry) = self.history.last() else { return };
+        #[cfg(feature = "sqlite")]
+        if let Some(result) = sqlite_store::append_history_db(entry, self.history[0].timestamp) {
+            if let Err(e) = result {
+                eprintln!("Failed to save listening history: {}", e);
+            }
+            return;
+        }
+        // history.jsonl is trimmed on load rather than on every listen
+        let path = paths::data_file(HISTORY_FILE);
+        let line = serde_json::to_string(entry).unwrap_or_default();
+        if let Err(e) = storage::append_line(&path, &line) {
+            eprintln!("Failed to save {:?}: {}", path, e);
+        }
+    }
+
+    /// Rewrites the whole history, after an import or trimming.
+    fn save_history(&self) {
+        #[cfg(feature = "sqlite")]
+        if let Some(result) = sqlite_store::replace_history_db(&self.history) {
+            if let Err(e) = result {
+                eprintln!("Failed to save listening history: {}", e);
+            }
+            return;
+        }
+        let path = paths::data_file(HISTORY_FILE);
+        let text: String = self.history.iter()
+            .map(|entry| serde_json::to_string(entry).unwrap_or_default() + "\n")
+            .collect();
+        if let Err(e) = storage::write_atomic(&path, text.as_bytes()) {
+            eprintln!("Failed to save {:?}: {}", path, e);
         }
     }
 
     fn save(&self) {
-        let path = get_config_path("playlists.json");
-        if let Ok(file) = File::create(&path) {
-            let _ = serde_json::to_writer(file, self);
+        #[cfg(feature = "sqlite")]
+        if let Some(result) = sqlite_store::save_db(self) {
+            if let Err(e) = result {
+                eprintln!("Failed to save library database: {}", e);
+            }
+            return;
         }
+        let path = paths::data_file("playlists.json");
+        if let Err(e) = storage::save_json(&path, self, false) {
+            eprintln!("Failed to save {:?}: {}", path, e);
+        }
+    }
+}
+
+static LIBRARY_INDEXING: AtomicBool = AtomicBool::new(false);
+
+// Reads tags for new playlist entries on a background thread, so adding a
+// whole folder doesn't block the UI or an API request.
+fn spawn_library_indexer(data: Arc<Mutex<PlaylistsManager>>) {
+    if LIBRARY_INDEXING.swap(true, Ordering::SeqCst) {
+        return;
     }
+    thread::spawn(move || {
+        // Playlist entries whose file is gone get no library entry, so smart
+        // playlists don't pick them up; skipped for the rest of this run
+        let mut skipped: HashSet<PathBuf> = HashSet::new();
+        let unindexed = |data: &PlaylistsManager, skipped: &HashSet<PathBuf>| -> Vec<PathBuf> {
+            data.unindexed_paths().into_iter().filter(|path| !skipped.contains(path)).collect()
+        };
+        loop {
+            let missing = unindexed(&data.lock().unwrap(), &skipped);
+            if missing.is_empty() {
+                LIBRARY_INDEXING.store(false, Ordering::SeqCst);
+                // Files may have been added between the check and the reset
+                if unindexed(&data.lock().unwrap(), &skipped).is_empty() || LIBRARY_INDEXING.swap(true, Ordering::SeqCst) {
+                    break;
+                }
+                continue;
+            }
+            // Save in chunks so search picks up results while a big import is still running
+            for chunk in missing.chunks(200) {
+                let (found, gone): (Vec<&PathBuf>, Vec<&PathBuf>) = chunk.iter().partition(|path| path.exists());
+                skipped.extend(gone.into_iter().cloned());
+                let now = unix_now();
+                let entries: Vec<(PathBuf, TrackMeta, Option<u64>)> = found.into_iter()
+                    .map(|path| (path.clone(), read_track_meta(path), file_modified(path)))
+                    .collect();
+                let mut data = data.lock().unwrap();
+                for (path, meta, modified) in entries {
+                    // Tracks added this session are indexed right after, so now is when they came in
+                    let added = if data.added_unknown.remove(&path) { modified.unwrap_or(now) } else { now };
+                    data.library.insert(path, TrackMeta { added, ..meta });
+                }
+                data.refresh_smart_playlists();
+                // Tag-based sort keys may have changed
+                for name in data.sort_orders.keys().cloned().collect::<Vec<_>>() {
+                    data.apply_sort(&name);
+                }
+                data.save();
+            }
+        }
+    });
+}
+
+// Drops library entries for deleted files, once at startup. Files whose
+// folder is gone too, such as on a drive that isn't mounted, are kept along
+// with their ratings and play counts.
+fn spawn_library_pruner(data: Arc<Mutex<PlaylistsManager>>) {
+    thread::spawn(move || {
+        let paths: Vec<PathBuf> = data.lock().unwrap().library.keys().cloned().collect();
+        let deleted: Vec<PathBuf> = paths.into_iter()
+            .filter(|path| !path.exists() && path.parent().is_some_and(Path::is_dir))
+            .collect();
+        if deleted.is_empty() {
+            return;
+        }
+        let mut data = data.lock().unwrap();
+        for path in &deleted {
+            data.library.remove(path);
+        }
+        data.refresh_smart_playlists();
+        data.save();
+    });
+}
+
+// Modification time of a file as a Unix timestamp
+fn file_modified(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 struct AppConfig {
+    #[serde(default)]
+    version: u32,
     port: u16,
     #[serde(default = "default_font_size")]
     lyrics_font_size: f32,
@@ -290,40 +1017,124 @@ struct AppConfig {
     lyrics_text_color: [u8; 4],
     #[serde(default = "default_bg_color")]
     lyrics_bg_color: [u8; 4],
-    #[serde(default = "default_ui_bg_color")]
-    ui_bg_color: [u8; 4],
+    // Sung part of lines with word timings (enhanced LRC)
+    #[serde(default = "default_highlight_color")]
+    lyrics_highlight_color: [u8; 4],
+    // Second line of bilingual lyrics
+    #[serde(default = "default_translation_font_size")]
+    lyrics_translation_font_size: f32,
+    #[serde(default = "default_translation_color")]
+    lyrics_translation_color: [u8; 4],
+    // Desktop lyrics window; the position is None until it has been moved
+    #[serde(default)]
+    desktop_lyrics_pos: Option<[f32; 2]>,
+    #[serde(default = "default_desktop_lyrics_size")]
+    desktop_lyrics_size: [f32; 2],
+    // Click-through and not draggable
+    #[serde(default)]
+    desktop_lyrics_locked: bool,
+    // Show the next line under the current one
+    #[serde(default)]
+    desktop_lyrics_two_lines: bool,
+    #[serde(default)]
+    desktop_lyrics_align: LyricsAlign,
+    #[serde(default)]
+    desktop_lyrics_outline: bool,
+    #[serde(default)]
+    desktop_lyrics_shadow: bool,
+    #[serde(default = "default_effect_color")]
+    desktop_lyrics_effect_color: [u8; 4],
+    #[serde(default)]
+    theme: Theme,
+    // Saved presets, besides the built-in dark and light ones
+    #[serde(default)]
+    themes: Vec<Theme>,
+    // Also store star ratings in the files' tags (POPM / FMPS_RATING)
+    #[serde(default)]
+    write_rating_tags: bool,
+    // Where to look for lyrics, in priority order
+    #[serde(default = "default_lyrics_sources")]
+    lyrics_sources: Vec<LyricsSource>,
+    #[serde(default)]
+    lyrics_dirs: Vec<PathBuf>,
+    // None picks a system font with Chinese glyphs
+    #[serde(default)]
+    ui_font: Option<FontChoice>,
+    // None uses the UI font
+    #[serde(default)]
+    lyrics_font: Option<FontChoice>,
+    // Code of a built-in or user language file, e.g. "en"
+    #[serde(default = "default_language")]
+    language: String,
+    // Action -> keys, e.g. "Ctrl+Right"; see shortcuts.rs
+    #[serde(default = "shortcuts::default_bindings")]
+    shortcuts: Bindings,
 }
 
 fn default_font_size() -> f32 { 32.0 }
 fn default_text_color() -> [u8; 4] { [255, 255, 255, 255] }
 fn default_bg_color() -> [u8; 4] { [0, 0, 0, 0] }
-fn default_ui_bg_color() -> [u8; 4] { [30, 30, 30, 255] }
+fn default_highlight_color() -> [u8; 4] { [255, 200, 60, 255] }
+fn default_translation_font_size() -> f32 { 22.0 }
+fn default_translation_color() -> [u8; 4] { [200, 200, 200, 255] }
+fn default_desktop_lyrics_size() -> [f32; 2] { [800.0, 100.0] }
+fn default_effect_color() -> [u8; 4] { [0, 0, 0, 200] }
+fn default_lyrics_sources() -> Vec<LyricsSource> { DEFAULT_LYRICS_SOURCES.to_vec() }
+fn default_language() -> String { "zh".to_string() }
 
-impl AppConfig {
-    fn load() -> Self {
-        let path = get_config_path("config.json");
-        if let Ok(file) = File::open(&path) {
-            if let Ok(config) = serde_json::from_reader(BufReader::new(file)) {
-                return config;
-            }
-        }
-        let config = Self { 
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
             port: 3000,
             lyrics_font_size: default_font_size(),
             lyrics_text_color: default_text_color(),
             lyrics_bg_color: default_bg_color(),
-            ui_bg_color: default_ui_bg_color(),
-        };
-        if let Ok(file) = File::create(&path) {
-            let _ = serde_json::to_writer_pretty(file, &config);
+            lyrics_highlight_color: default_highlight_color(),
+            lyrics_translation_font_size: default_translation_font_size(),
+            lyrics_translation_color: default_translation_color(),
+            desktop_lyrics_pos: None,
+            desktop_lyrics_size: default_desktop_lyrics_size(),
+            desktop_lyrics_locked: false,
+            desktop_lyrics_two_lines: false,
+            desktop_lyrics_align: LyricsAlign::default(),
+            desktop_lyrics_outline: false,
+            desktop_lyrics_shadow: false,
+            desktop_lyrics_effect_color: default_effect_color(),
+            theme: Theme::default(),
+            themes: Vec::new(),
+            write_rating_tags: false,
+            lyrics_sources: default_lyrics_sources(),
+            lyrics_dirs: Vec::new(),
+            ui_font: None,
+            lyrics_font: None,
+            language: default_language(),
+            shortcuts: shortcuts::default_bindings(),
+        }
+    }
+}
+
+impl AppConfig {
+    fn load() -> Self {
+        Self::load_with_error().0
+    }
+
+    /// Like `load`, but also returns a message if the file was unreadable.
+    fn load_with_error() -> (Self, Option<String>) {
+        let path = paths::config_file("config.json");
+        let (config, error) = storage::load_versioned(&path, CONFIG_VERSION, migrate_config);
+        if let Some(config) = config {
+            return (config, error);
         }
-        config
+        let config = Self::default();
+        config.save();
+        (config, error)
     }
 
     fn save(&self) {
-        let path = get_config_path("config.json");
-        if let Ok(file) = File::create(&path) {
-            let _ = serde_json::to_writer_pretty(file, self);
+        let path = paths::config_file("config.json");
+        if let Err(e) = storage::save_json(&path, self, true) {
+            eprintln!("Failed to save {:?}: {}", path, e);
         }
     }
 }
@@ -334,6 +1145,40 @@ impl AppConfig {
 struct AppState {
     audio_tx: Sender<AudioCommand>,
     data: Arc<Mutex<PlaylistsManager>>,
+    now_playing: Arc<Mutex<NowPlaying>>,
+    // The "write ratings to file tags" setting, shared with the settings window
+    write_rating_tags: Arc<AtomicBool>,
+    // The lyrics sources and folders, shared with the settings window
+    lyrics_lookup: Arc<Mutex<LyricsLookup>>,
+}
+
+// Where lyrics are looked for, see lyrics::resolve
+#[derive(Clone)]
+struct LyricsLookup {
+    sources: Vec<LyricsSource>,
+    dirs: Vec<PathBuf>,
+}
+
+// What the UI is playing, kept up to date for API clients
+#[derive(Default)]
+struct NowPlaying {
+    path: Option<PathBuf>,
+    position: Duration,
+    is_playing: bool,
+    // When `position` was reported, to interpolate between updates
+    synced_at: Option<Instant>,
+    lyrics: Lyrics,
+    // Set when PUT /lyrics replaced the playing track's lyrics, for the UI to pick up
+    lyrics_replaced: bool,
+}
+
+impl NowPlaying {
+    fn position(&self) -> Duration {
+        match self.synced_at {
+            Some(synced_at) if self.is_playing => self.position + synced_at.elapsed(),
+            _ => self.position,
+        }
+    }
 }
 
 // --- API Models ---
@@ -351,6 +1196,59 @@ struct RemoveRequest {
     playlist: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct BulkRemoveRequest {
+    indices: Vec<usize>,
+    playlist: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BulkTransferRequest {
+    indices: Vec<usize>,
+    from: Option<String>,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct QueueAddRequest {
+    indices: Vec<usize>,
+    playlist: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ImportPlaylistRequest {
+    // The playlist file's text, sent in the body rather than read from a path on the server
+    content: String,
+    format: String,
+    name: Option<String>,
+    // Folder relative entries are resolved against; they're skipped without one
+    base_dir: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExportPlaylistRequest {
+    playlist: Option<String>,
+    // Defaults to m3u8
+    format: Option<String>,
+    // Paths under this folder are written relative to it
+    base_dir: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExportPlaylistResponse {
+    playlist: String,
+    format: String,
+    count: usize,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct SmartPlaylistRequest {
+    name: String,
+    #[serde(flatten)]
+    playlist: SmartPlaylist,
+}
+
 #[derive(Deserialize)]
 struct RenamePlaylistRequest {
     old_name: String,
@@ -367,18 +1265,139 @@ struct SwitchPlaylistRequest {
     name: String,
 }
 
-#[derive(Serialize)]
-struct PlaylistFile {
-    path: String,
-    name: String,
-    exists: bool,
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<usize>,
 }
 
-#[derive(Serialize)]
-struct PlaylistResponse {
-    current: String,
-    files: Vec<PlaylistFile>,
-    all_playlists: Vec<String>,
+// Identifies a track by path, or by index into a playlist (default: current)
+#[derive(Deserialize)]
+struct RateRequest {
+    path: Option<String>,
+    index: Option<usize>,
+    playlist: Option<String>,
+    rating: Option<u8>,
+    favourite: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct TagsQuery {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct WriteTagsRequest {
+    paths: Vec<String>,
+    #[serde(flatten)]
+    changes: TagChanges,
+}
+
+#[derive(Serialize)]
+struct TagsResponse {
+    path: String,
+    #[serde(flatten)]
+    fields: TagFields,
+    cover: Option<CoverInfo>,
+}
+
+#[derive(Deserialize)]
+struct LyricsQuery {
+    // Defaults to the playing track
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LyricsResponse {
+    path: String,
+    #[serde(flatten)]
+    lyrics: Lyrics,
+}
+
+#[derive(Serialize)]
+struct CurrentLyricsResponse {
+    path: String,
+    position_ms: u64,
+    is_playing: bool,
+    current: Option<LyricLine>,
+    next: Option<LyricLine>,
+}
+
+#[derive(Deserialize)]
+struct PutLyricsRequest {
+    // Defaults to the playing track
+    path: Option<String>,
+    // LRC text, saved as the track's sibling .lrc
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TopQuery {
+    limit: Option<usize>,
+    // Count plays from the history of the last N days instead of all-time play counts
+    days: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct HistoryItem {
+    path: String,
+    title: String,
+    artist: String,
+    timestamp: u64,
+    listened_secs: u64,
+    skipped: bool,
+}
+
+#[derive(Serialize)]
+struct TopTrack {
+    path: String,
+    title: String,
+    artist: String,
+    play_count: u32,
+    last_played: u64,
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    playlist: Option<String>,
+    index: Option<usize>,
+    path: String,
+    name: String,
+    title: String,
+    artist: String,
+    album: String,
+    score: i64,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    query: String,
+    results: Vec<SearchHit>,
+}
+
+#[derive(Serialize)]
+struct PlaylistFile {
+    path: String,
+    name: String,
+    exists: bool,
+}
+
+#[derive(Serialize)]
+struct PlaylistResponse {
+    current: String,
+    files: Vec<PlaylistFile>,
+    all_playlists: Vec<String>,
+    smart_playlists: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct QueueResponse {
+    files: Vec<PlaylistFile>,
 }
 
 // --- API Handlers ---
@@ -389,6 +1408,7 @@ async fn api_play(
 ) -> Json<String> {
     let mut data = state.data.lock().unwrap();
     let target_list_name = payload.playlist.clone().unwrap_or_else(|| data.current_name.clone());
+    let is_smart = data.is_smart(&target_list_name);
     let list = data.lists.entry(target_list_name.clone()).or_default();
 
     let path_to_play = if let Some(idx) = payload.index {
@@ -396,17 +1416,15 @@ async fn api_play(
     } else if let Some(path_str) = payload.path {
         let path = PathBuf::from(path_str);
         if path.exists() {
-            if !list.iter().any(|item| item.path == path) {
+            // Smart playlists are computed, so the file is just played
+            if !is_smart && !list.iter().any(|item| item.path == path) {
                 let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                let mut final_name = name.clone();
-                let mut count = 1;
-                while list.iter().any(|item| item.name == final_name) {
-                    final_name = format!("{} ({})", name, count);
-                    count += 1;
-                }
+                let final_name = unique_item_name(list, &name);
                 // Insert at top (newest first)
                 list.insert(0, PlaylistItem { path: path.clone(), name: final_name });
+                data.apply_sort(&target_list_name);
                 data.save();
+                spawn_library_indexer(state.data.clone());
             }
             Some(path)
         } else {
@@ -432,6 +1450,9 @@ async fn api_remove_from_playlist(
 ) -> Json<String> {
     let mut data = state.data.lock().unwrap();
     let target_list_name = payload.playlist.unwrap_or_else(|| data.current_name.clone());
+    if data.is_smart(&target_list_name) {
+        return Json("Smart playlists are read-only".to_string());
+    }
     
     if let Some(list) = data.lists.get_mut(&target_list_name) {
         if payload.index < list.len() {
@@ -446,6 +1467,391 @@ async fn api_remove_from_playlist(
     }
 }
 
+async fn api_remove_many(
+    State(state): State<AppState>,
+    Json(payload): Json<BulkRemoveRequest>,
+) -> Json<String> {
+    let mut data = state.data.lock().unwrap();
+    let target_list_name = payload.playlist.unwrap_or_else(|| data.current_name.clone());
+    if !data.lists.contains_key(&target_list_name) {
+        return Json("Playlist not found".to_string());
+    }
+    if data.is_smart(&target_list_name) {
+        return Json("Smart playlists are read-only".to_string());
+    }
+    let removed = data.remove_items(&target_list_name, &payload.indices);
+    data.save();
+    Json(format!("Removed {} items from {}", removed, target_list_name))
+}
+
+fn transfer_items(state: AppState, payload: BulkTransferRequest, remove_source: bool) -> Json<String> {
+    let mut data = state.data.lock().unwrap();
+    let from = payload.from.unwrap_or_else(|| data.current_name.clone());
+    if !data.lists.contains_key(&from) || !data.lists.contains_key(&payload.to) {
+        return Json("Playlist not found".to_string());
+    }
+    if from == payload.to {
+        return Json("Source and target playlist are the same".to_string());
+    }
+    if data.is_smart(&payload.to) || (remove_source && data.is_smart(&from)) {
+        return Json("Smart playlists are read-only".to_string());
+    }
+    let added = data.copy_items(&from, &payload.indices, &payload.to);
+    if remove_source {
+        data.remove_items(&from, &payload.indices);
+    }
+    data.save();
+    let verb = if remove_source { "Moved" } else { "Copied" };
+    Json(format!("{} {} items from {} to {}", verb, added, from, payload.to))
+}
+
+async fn api_move_items(
+    State(state): State<AppState>,
+    Json(payload): Json<BulkTransferRequest>,
+) -> Json<String> {
+    transfer_items(state, payload, true)
+}
+
+async fn api_copy_items(
+    State(state): State<AppState>,
+    Json(payload): Json<BulkTransferRequest>,
+) -> Json<String> {
+    transfer_items(state, payload, false)
+}
+
+async fn api_queue_add(
+    State(state): State<AppState>,
+    Json(payload): Json<QueueAddRequest>,
+) -> Json<String> {
+    let mut data = state.data.lock().unwrap();
+    let target_list_name = payload.playlist.unwrap_or_else(|| data.current_name.clone());
+    if !data.lists.contains_key(&target_list_name) {
+        return Json("Playlist not found".to_string());
+    }
+    let queued = data.queue_items(&target_list_name, &payload.indices);
+    data.save();
+    Json(format!("Queued {} items", queued))
+}
+
+async fn api_import_playlist(
+    State(state): State<AppState>,
+    Json(payload): Json<ImportPlaylistRequest>,
+) -> Json<String> {
+    let Some(format) = PlaylistFormat::from_name(&payload.format) else {
+        return Json(format!("Unknown format: {}", payload.format));
+    };
+    let base_dir = payload.base_dir.map(PathBuf::from).unwrap_or_default();
+    let paths: Vec<PathBuf> = playlist_formats::parse_playlist(&payload.content, format, &base_dir)
+        .into_iter()
+        .filter(|path| path.is_absolute())
+        .collect();
+    let mut data = state.data.lock().unwrap();
+    match data.import_playlist(paths, payload.name, "Imported") {
+        Ok((name, added)) => {
+            data.save();
+            drop(data);
+            spawn_library_indexer(state.data.clone());
+            Json(format!("Imported {} items into {}", added, name))
+        }
+        Err(e) => Json(format!("Import failed: {}", e)),
+    }
+}
+
+// Returns the text instead of writing a file, see export_playlist_file
+async fn api_export_playlist(
+    State(state): State<AppState>,
+    Json(payload): Json<ExportPlaylistRequest>,
+) -> Result<Json<ExportPlaylistResponse>, Json<String>> {
+    let data = state.data.lock().unwrap();
+    let target_list_name = payload.playlist.unwrap_or_else(|| data.current_name.clone());
+    let format = match payload.format.as_deref() {
+        Some(name) => PlaylistFormat::from_name(name).ok_or_else(|| Json(format!("Unknown format: {}", name)))?,
+        None => PlaylistFormat::M3u8,
+    };
+    let items = data.export_items(&target_list_name).ok_or_else(|| Json("Playlist not found".to_string()))?;
+    let base_dir = payload.base_dir.map(PathBuf::from);
+    let content = playlist_formats::render_playlist(&target_list_name, &items, format, base_dir.as_deref());
+    Ok(Json(ExportPlaylistResponse {
+        playlist: target_list_name,
+        format: format.extension().to_string(),
+        count: items.len(),
+        content,
+    }))
+}
+
+// Whole-library backup in the playlists.json format, sent in the body rather
+// than read from or written to a path on the server
+async fn api_export_library(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let data = state.data.lock().unwrap();
+    let mut document = serde_json::to_value(&*data).unwrap_or_default();
+    document["history"] = serde_json::to_value(&data.history).unwrap_or_default();
+    Json(document)
+}
+
+async fn api_import_library(
+    State(state): State<AppState>,
+    Json(document): Json<serde_json::Value>,
+) -> Json<String> {
+    let imported: PlaylistsManager = match storage::from_versioned_value(document, PLAYLISTS_VERSION, migrate_playlists) {
+        Ok(imported) => imported,
+        Err(e) => return Json(format!("Import failed: {}", e)),
+    };
+    let mut data = state.data.lock().unwrap();
+    *data = imported;
+    if !data.lists.contains_key(&data.current_name) {
+        let name = data.lists.keys().next().cloned().unwrap_or_else(|| "Default List".to_string());
+        data.lists.entry(name.clone()).or_default();
+        data.current_name = name;
+    }
+    data.refresh_smart_playlists();
+    data.save();
+    data.save_history();
+    let message = format!("Imported {} playlists and {} library entries", data.lists.len(), data.library.len());
+    drop(data);
+    spawn_library_indexer(state.data.clone());
+    Json(message)
+}
+
+async fn api_save_smart_playlist(
+    State(state): State<AppState>,
+    Json(payload): Json<SmartPlaylistRequest>,
+) -> Json<String> {
+    let mut data = state.data.lock().unwrap();
+    if data.lists.contains_key(&payload.name) && !data.is_smart(&payload.name) {
+        return Json("A regular playlist with this name already exists".to_string());
+    }
+    if data.is_builtin(&payload.name) {
+        return Json("Built-in playlists cannot be edited".to_string());
+    }
+    data.smart.insert(payload.name.clone(), payload.playlist);
+    data.refresh_smart_playlists();
+    data.save();
+    let count = data.lists.get(&payload.name).map_or(0, |list| list.len());
+    Json(format!("Smart playlist {} has {} items", payload.name, count))
+}
+
+async fn api_rate(
+    State(state): State<AppState>,
+    Json(payload): Json<RateRequest>,
+) -> Json<String> {
+    if payload.rating.is_some_and(|rating| rating > 5) {
+        return Json("Rating must be between 0 and 5".to_string());
+    }
+    let path = {
+        let data = state.data.lock().unwrap();
+        let path = if let Some(path) = payload.path {
+            PathBuf::from(path)
+        } else if let Some(index) = payload.index {
+            let target_list_name = payload.playlist.unwrap_or_else(|| data.current_name.clone());
+            match data.lists.get(&target_list_name).and_then(|list| list.get(index)) {
+                Some(item) => item.path.clone(),
+                None => return Json("Index out of bounds".to_string()),
+            }
+        } else {
+            return Json("Either path or index is required".to_string());
+        };
+        if !data.has_track(&path) {
+            return Json("Only tracks in the library or a playlist can be rated".to_string());
+        }
+        path
+    };
+
+    let paths = std::slice::from_ref(&path);
+    let fresh = read_unindexed_meta(&state.data, paths);
+    let mut data = state.data.lock().unwrap();
+    let changed = data.rate_tracks(paths, fresh, payload.rating, payload.favourite);
+    spawn_rating_tag_writes(&data, changed, state.write_rating_tags.load(Ordering::Relaxed));
+    data.save();
+    let meta = data.library.get(&path).cloned().unwrap_or_default();
+    Json(format!("Rating {}, favourite {}", meta.rating, meta.favourite))
+}
+
+async fn api_get_tags(
+    State(state): State<AppState>,
+    Query(query): Query<TagsQuery>,
+) -> Result<Json<TagsResponse>, Json<String>> {
+    if !state.data.lock().unwrap().has_track(Path::new(&query.path)) {
+        return Err(Json(format!("Not in the library: {:?}", query.path)));
+    }
+    match tags::read_tags(Path::new(&query.path)) {
+        Ok((fields, cover)) => Ok(Json(TagsResponse { path: query.path, fields, cover })),
+        Err(e) => Err(Json(format!("Failed to read tags: {}", e))),
+    }
+}
+
+async fn api_get_lyrics(
+    State(state): State<AppState>,
+    Query(query): Query<LyricsQuery>,
+) -> Result<Json<LyricsResponse>, Json<String>> {
+    let (playing, playing_lyrics) = {
+        let now_playing = state.now_playing.lock().unwrap();
+        (now_playing.path.clone(), now_playing.lyrics.clone())
+    };
+    let path = match query.path.map(PathBuf::from).or_else(|| playing.clone()) {
+        Some(path) => path,
+        None => return Err(Json("Nothing is playing".to_string())),
+    };
+    let lyrics = if playing.as_ref() == Some(&path) {
+        playing_lyrics
+    } else {
+        if !state.data.lock().unwrap().has_track(&path) {
+            return Err(Json(format!("Not in the library: {:?}", path)));
+        }
+        let lookup = state.lyrics_lookup.lock().unwrap().clone();
+        lyrics::load(&path, &lookup.sources, &lookup.dirs)
+    };
+    if lyrics.is_empty() {
+        return Err(Json("No lyrics found".to_string()));
+    }
+    Ok(Json(LyricsResponse { path: path.to_string_lossy().to_string(), lyrics }))
+}
+
+async fn api_current_lyrics(State(state): State<AppState>) -> Result<Json<CurrentLyricsResponse>, Json<String>> {
+    let now_playing = state.now_playing.lock().unwrap();
+    let Some(path) = &now_playing.path else {
+        return Err(Json("Nothing is playing".to_string()));
+    };
+    let position = now_playing.position();
+    let lines = &now_playing.lyrics.lines;
+    let current = now_playing.lyrics.line_at(position);
+    let next_index = current.map_or(0, |i| i + 1);
+    Ok(Json(CurrentLyricsResponse {
+        path: path.to_string_lossy().to_string(),
+        position_ms: position.as_millis() as u64,
+        is_playing: now_playing.is_playing,
+        current: current.map(|i| lines[i].clone()),
+        next: lines.get(next_index).cloned(),
+    }))
+}
+
+async fn api_put_lyrics(
+    State(state): State<AppState>,
+    Json(payload): Json<PutLyricsRequest>,
+) -> Json<String> {
+    let playing = state.now_playing.lock().unwrap().path.clone();
+    let Some(path) = payload.path.map(PathBuf::from).or_else(|| playing.clone()) else {
+        return Json("Nothing is playing".to_string());
+    };
+    if !state.data.lock().unwrap().has_track(&path) {
+        return Json(format!("Not in the library: {:?}", path));
+    }
+    if !path.exists() {
+        return Json(format!("File not found: {:?}", path));
+    }
+    let line_count = parse_lrc(&payload.content).lines.len();
+    if line_count == 0 {
+        return Json("No timed lines in the LRC".to_string());
+    }
+    let lrc_path = path.with_extension("lrc");
+    if let Err(e) = storage::replace_file(&lrc_path, payload.content.as_bytes()) {
+        return Json(format!("Failed to save {:?}: {}", lrc_path, e));
+    }
+    if playing.as_ref() == Some(&path) {
+        // Same as loading them from the file, so a .trans.lrc still shows up
+        let dirs = state.lyrics_lookup.lock().unwrap().dirs.clone();
+        let lyrics = lyrics::parse_with_translation(&payload.content, &path, &dirs);
+        let mut now_playing = state.now_playing.lock().unwrap();
+        if now_playing.path.as_ref() == Some(&path) {
+            now_playing.lyrics = lyrics;
+            now_playing.lyrics_replaced = true;
+        }
+    }
+    Json(format!("Saved {} lines to {:?}", line_count, lrc_path))
+}
+
+async fn api_write_tags(
+    State(state): State<AppState>,
+    Json(payload): Json<WriteTagsRequest>,
+) -> Json<String> {
+    if payload.changes.is_empty() {
+        return Json("No changes".to_string());
+    }
+    let paths: Vec<PathBuf> = payload.paths.iter().map(PathBuf::from).collect();
+    let unknown: Vec<&PathBuf> = {
+        let data = state.data.lock().unwrap();
+        paths.iter().filter(|path| !data.has_track(path)).collect()
+    };
+    if !unknown.is_empty() {
+        return Json(format!("Not in the library: {:?}", unknown));
+    }
+    let errors = write_tags_and_reindex(&state.data, &paths, &payload.changes);
+    if errors.is_empty() {
+        Json(format!("Updated {} files", paths.len()))
+    } else {
+        Json(format!("Updated {} files, failed: {}", paths.len() - errors.len(), errors.join("; ")))
+    }
+}
+
+async fn api_get_history(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<Vec<HistoryItem>> {
+    let data = state.data.lock().unwrap();
+    let limit = query.limit.unwrap_or(50);
+    let items = data.history.iter().rev().take(limit)
+        .map(|entry| {
+            let meta = data.library.get(&entry.path).cloned().unwrap_or_default();
+            HistoryItem {
+                path: entry.path.to_string_lossy().to_string(),
+                title: meta.title,
+                artist: meta.artist,
+                timestamp: entry.timestamp,
+                listened_secs: entry.listened_secs,
+                skipped: entry.skipped,
+            }
+        }).collect();
+    Json(items)
+}
+
+async fn api_stats_top(
+    State(state): State<AppState>,
+    Query(query): Query<TopQuery>,
+) -> Json<Vec<TopTrack>> {
+    let data = state.data.lock().unwrap();
+    let limit = query.limit.unwrap_or(10);
+    let mut counts: Vec<(&PathBuf, u32, u64)> = match query.days {
+        Some(days) => {
+            let since = unix_now().saturating_sub(days * 86400);
+            let mut window: HashMap<&PathBuf, (u32, u64)> = HashMap::new();
+            for entry in data.history.iter().filter(|e| !e.skipped && e.timestamp >= since) {
+                let counted = window.entry(&entry.path).or_default();
+                counted.0 += 1;
+                counted.1 = counted.1.max(entry.timestamp);
+            }
+            window.into_iter().map(|(path, (count, last))| (path, count, last)).collect()
+        }
+        None => data.library.iter()
+            .filter(|(_, meta)| meta.play_count > 0)
+            .map(|(path, meta)| (path, meta.play_count, meta.last_played))
+            .collect(),
+    };
+    // Most plays first, most recent breaks ties
+    counts.sort_by_key(|&(_, count, last)| std::cmp::Reverse((count, last)));
+    let top = counts.into_iter().take(limit)
+        .map(|(path, play_count, last_played)| {
+            let meta = data.library.get(path).cloned().unwrap_or_default();
+            TopTrack {
+                path: path.to_string_lossy().to_string(),
+                title: meta.title,
+                artist: meta.artist,
+                play_count,
+                last_played,
+            }
+        }).collect();
+    Json(top)
+}
+
+async fn api_get_queue(State(state): State<AppState>) -> Json<QueueResponse> {
+    let data = state.data.lock().unwrap();
+    let files = data.queue.iter()
+        .map(|path| PlaylistFile {
+            path: path.to_string_lossy().to_string(),
+            name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            exists: path.exists(),
+        }).collect();
+    Json(QueueResponse { files })
+}
+
 async fn api_get_playlist(State(state): State<AppState>) -> Json<PlaylistResponse> {
     let data = state.data.lock().unwrap();
     let current = data.current_name.clone();
@@ -456,8 +1862,67 @@ async fn api_get_playlist(State(state): State<AppState>) -> Json<PlaylistRespons
             exists: p.path.exists()
         }).collect();
     let all_playlists = data.lists.keys().cloned().collect();
+    let smart_playlists = data.smart.keys().cloned().collect();
     
-    Json(PlaylistResponse { current, files, all_playlists })
+    Json(PlaylistResponse { current, files, all_playlists, smart_playlists })
+}
+
+async fn api_search(
+    State(state): State<AppState>,
+    Query(params): Query<SearchQuery>,
+) -> Json<SearchResponse> {
+    let data = state.data.lock().unwrap();
+    let mut results = Vec::new();
+
+    let mut names: Vec<&String> = data.lists.keys().collect();
+    names.sort();
+    for name in names {
+        for (index, item) in data.lists[name].iter().enumerate() {
+            let meta = data.library.get(&item.path);
+            if let Some(score) = fuzzy_score(&params.q, &search_haystack(item, meta)) {
+                let meta = meta.cloned().unwrap_or_default();
+                results.push(SearchHit {
+                    playlist: Some(name.clone()),
+                    index: Some(index),
+                    path: item.path.to_string_lossy().to_string(),
+                    name: item.name.clone(),
+                    title: meta.title,
+                    artist: meta.artist,
+                    album: meta.album,
+                    score,
+                });
+            }
+        }
+    }
+
+    // Library entries that are no longer part of any playlist
+    let listed: HashSet<&PathBuf> = data.lists.values().flatten().map(|item| &item.path).collect();
+    for (path, meta) in &data.library {
+        if listed.contains(path) {
+            continue;
+        }
+        let item = PlaylistItem {
+            path: path.clone(),
+            name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        };
+        if let Some(score) = fuzzy_score(&params.q, &search_haystack(&item, Some(meta))) {
+            results.push(SearchHit {
+                playlist: None,
+                index: None,
+                path: path.to_string_lossy().to_string(),
+                name: item.name,
+                title: meta.title.clone(),
+                artist: meta.artist.clone(),
+                album: meta.album.clone(),
+                score,
+            });
+        }
+    }
+
+    // Stable sort keeps playlist order for equal scores
+    results.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    results.truncate(params.limit.unwrap_or(50));
+    Json(SearchResponse { query: params.q, results })
 }
 
 async fn api_rename_playlist(
@@ -468,12 +1933,21 @@ async fn api_rename_playlist(
     if !data.lists.contains_key(&payload.old_name) {
         return Json("Playlist not found".to_string());
     }
+    if data.is_builtin(&payload.old_name) {
+        return Json("Built-in playlists cannot be renamed".to_string());
+    }
     if data.lists.contains_key(&payload.new_name) {
         return Json("New name already exists".to_string());
     }
     
     if let Some(list) = data.lists.remove(&payload.old_name) {
         data.lists.insert(payload.new_name.clone(), list);
+        if let Some(sort) = data.sort_orders.remove(&payload.old_name) {
+            data.sort_orders.insert(payload.new_name.clone(), sort);
+        }
+        if let Some(smart) = data.smart.remove(&payload.old_name) {
+            data.smart.insert(payload.new_name.clone(), smart);
+        }
         if data.current_name == payload.old_name {
             data.current_name = payload.new_name;
         }
@@ -492,8 +1966,13 @@ async fn api_delete_playlist(
     if data.lists.len() <= 1 {
         return Json("Cannot delete the last playlist".to_string());
     }
+    if data.is_builtin(&payload.name) {
+        return Json("Built-in playlists cannot be deleted".to_string());
+    }
     
     if data.lists.remove(&payload.name).is_some() {
+        data.sort_orders.remove(&payload.name);
+        data.smart.remove(&payload.name);
         if data.current_name == payload.name {
             if let Some(first) = data.lists.keys().next().cloned() {
                 data.current_name = first;
@@ -535,67 +2014,186 @@ enum PlaybackMode {
 
 impl PlaybackMode {
     fn as_str(&self, lang: Language) -> &'static str {
-        match lang {
-            Language::Chinese => match self {
-                PlaybackMode::Order => "顺序播放",
-                PlaybackMode::ListLoop => "列表循环",
-                PlaybackMode::SingleLoop => "单曲循环",
-                PlaybackMode::Single => "单曲播放",
-            },
-            Language::English => match self {
-                PlaybackMode::Order => "Order",
-                PlaybackMode::ListLoop => "List Loop",
-                PlaybackMode::SingleLoop => "Single Loop",
-                PlaybackMode::Single => "Single",
-            },
-        }
+        lang.tr(match self {
+            PlaybackMode::Order => "playback_mode.order",
+            PlaybackMode::ListLoop => "playback_mode.list_loop",
+            PlaybackMode::SingleLoop => "playback_mode.single_loop",
+            PlaybackMode::Single => "playback_mode.single",
+        })
     }
 }
 
 // --- Helper Functions ---
 
-fn parse_lrc(content: &str) -> Vec<(Duration, String)> {
-    let mut lyrics = Vec::new();
-    for line in content.lines() {
-        if let Some(start) = line.find('[') {
-            if let Some(end) = line.find(']') {
-                let time_part = &line[start+1..end];
-                let text_part = &line[end+1..];
-                let parts: Vec<&str> = time_part.split(':').collect();
-                if parts.len() == 2 {
-                    if let Ok(min) = parts[0].parse::<u64>() {
-                        let sec_parts: Vec<&str> = parts[1].split('.').collect();
-                        if !sec_parts.is_empty() {
-                             if let Ok(sec) = sec_parts[0].parse::<u64>() {
-                                 let millis = if sec_parts.len() > 1 {
-                                     let m_str = sec_parts[1];
-                                     let m = m_str.parse::<u64>().unwrap_or(0);
-                                     // .xx is hundredths (10ms), .xxx is millis (1ms)
-                                     if m_str.len() == 2 { m * 10 } else { m }
-                                 } else { 0 };
-                                 
-                                 let duration = Duration::from_secs(min * 60 + sec) + Duration::from_millis(millis);
-                                 lyrics.push((duration, text_part.trim().to_string()));
-                             }
-                        }
-                    }
-                }
-            }
+// Text a playlist entry can be found by: its display name plus the cached tags
+fn search_haystack(item: &PlaylistItem, meta: Option<&TrackMeta>) -> String {
+    match meta {
+        Some(meta) => format!("{} {} {} {} {}", item.name, meta.title, meta.artist, meta.album, meta.genre),
+        None => item.name.clone(),
+    }
+}
+
+// Case-insensitive fuzzy match. Every whitespace separated term of the query must
+// appear in `text` as a subsequence; substrings, consecutive characters and word
+// starts score higher. Returns None if any term doesn't match.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let haystack: Vec<char> = text.to_lowercase().chars().collect();
+    let mut total = 0;
+    let mut terms = 0;
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.to_lowercase().chars().collect();
+        total += fuzzy_term_score(&term, &haystack)?;
+        terms += 1;
+    }
+    if terms == 0 { None } else { Some(total) }
+}
+
+fn fuzzy_term_score(term: &[char], haystack: &[char]) -> Option<i64> {
+    if let Some(pos) = haystack.windows(term.len()).position(|w| w == term) {
+        let word_start = pos == 0 || !haystack[pos - 1].is_alphanumeric();
+        let bonus = if word_start { 50 } else { 0 };
+        return Some(100 + bonus + term.len() as i64 * 10 - pos.min(50) as i64);
+    }
+
+    let mut score = 0;
+    let mut from = 0;
+    let mut prev: Option<usize> = None;
+    for &c in term {
+        let found = from + haystack[from..].iter().position(|&h| h == c)?;
+        score += 1;
+        if prev.is_some_and(|p| p + 1 == found) {
+            score += 5;
         }
+        if found == 0 || !haystack[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        prev = Some(found);
+        from = found + 1;
     }
-    lyrics.sort_by_key(|k| k.0);
-    lyrics
+    Some(score)
 }
 
-fn scroll_label(ui: &mut egui::Ui, text: &str, max_width: f32, should_scroll: bool, time: f64) {
-    let font_id = egui::FontId::proportional(14.0);
-    let text_galley = ui.painter().layout_no_wrap(text.to_string(), font_id.clone(), ui.visuals().text_color());
-    let text_width = text_galley.size().x;
+fn reveal_in_file_manager(path: &Path) {
+    #[cfg(target_os = "windows")]
+    let result = {
+        use std::os::windows::process::CommandExt;
+        std::process::Command::new("explorer").raw_arg(format!("/select,\"{}\"", path.display())).spawn()
+    };
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg("-R").arg(path).spawn();
+    // Most Linux file managers can't select a file, so open the folder instead
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let result = std::process::Command::new("xdg-open").arg(path.parent().unwrap_or(path)).spawn();
+    if let Err(e) = result {
+        eprintln!("Failed to open file manager for {:?}: {}", path, e);
+    }
+}
 
-    if text_width <= max_width || !should_scroll {
-        ui.add(egui::Label::new(egui::RichText::new(text)).truncate());
-    } else {
-        let speed = 30.0; 
+// Playlist table columns: #, title, artist, album, duration, rating, plays, last played, path.
+// `None` marks the index column, which keeps the list's own order.
+const PLAYLIST_COLUMNS: [Option<SortColumn>; 9] = [
+    None,
+    Some(SortColumn::Title),
+    Some(SortColumn::Artist),
+    Some(SortColumn::Album),
+    Some(SortColumn::Duration),
+    Some(SortColumn::Rating),
+    Some(SortColumn::PlayCount),
+    Some(SortColumn::LastPlayed),
+    Some(SortColumn::Path),
+];
+
+fn playlist_column_rects(rect: egui::Rect) -> [egui::Rect; 9] {
+    let index_width = 36.0;
+    let duration_width = 48.0;
+    let rating_width = 72.0;
+    let plays_width = 40.0;
+    let last_played_width = 80.0;
+    let fixed = index_width + duration_width + rating_width + plays_width + last_played_width;
+    let flexible = (rect.width() - fixed).max(0.0);
+    let widths = [
+        index_width,
+        flexible * 0.35,
+        flexible * 0.2,
+        flexible * 0.2,
+        duration_width,
+        rating_width,
+        plays_width,
+        last_played_width,
+        flexible * 0.25,
+    ];
+    let mut x = rect.left();
+    widths.map(|width| {
+        let cell = egui::Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(width, rect.height()));
+        x += width;
+        cell
+    })
+}
+
+// Single line of text, vertically centered and clipped to the cell
+fn paint_cell_text(ui: &egui::Ui, rect: egui::Rect, text: &str, color: egui::Color32) {
+    let galley = ui.painter().layout_no_wrap(text.to_string(), egui::FontId::proportional(14.0), color);
+    let pos = rect.min + egui::vec2(2.0, (rect.height() - galley.size().y) / 2.0);
+    ui.painter().with_clip_rect(rect.shrink2(egui::vec2(2.0, 0.0))).galley(pos, galley, egui::Color32::TRANSPARENT);
+}
+
+// "♥★★★" style, empty when unrated
+fn format_rating(meta: &TrackMeta) -> String {
+    let heart = if meta.favourite { "♥" } else { "" };
+    format!("{}{}", heart, "★".repeat(meta.rating.min(5) as usize))
+}
+
+fn format_track_duration(secs: u64) -> String {
+    if secs == 0 {
+        return "--:--".to_string();
+    }
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+// "5 min ago" up to a month, then the date
+fn format_last_played(timestamp: u64, language: Language) -> String {
+    if timestamp == 0 {
+        return "-".to_string();
+    }
+    let ago = unix_now().saturating_sub(timestamp);
+    let (minutes, hours, days) = (ago / 60, ago / 3600, ago / 86400);
+    if minutes < 1 {
+        language.tr("just_now").to_string()
+    } else if hours < 1 {
+        language.trf("minutes_ago", &[("n", &minutes)])
+    } else if days < 1 {
+        language.trf("hours_ago", &[("n", &hours)])
+    } else if days < 30 {
+        language.trf("days_ago", &[("n", &days)])
+    } else {
+        format_date(timestamp)
+    }
+}
+
+// YYYY-MM-DD (UTC) from a Unix timestamp
+fn format_date(timestamp: u64) -> String {
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = (timestamp / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn scroll_label(ui: &mut egui::Ui, text: &str, max_width: f32, should_scroll: bool, time: f64) {
+    let font_id = egui::FontId::proportional(14.0);
+    let text_galley = ui.painter().layout_no_wrap(text.to_string(), font_id.clone(), ui.visuals().text_color());
+    let text_width = text_galley.size().x;
+
+    if text_width <= max_width || !should_scroll {
+        ui.add(egui::Label::new(egui::RichText::new(text)).truncate());
+    } else {
+        let speed = 30.0; 
         let gap = 50.0;
         let cycle_len = text_width + gap;
         let offset = (time * speed as f64) % cycle_len as f64;
@@ -626,19 +2224,346 @@ fn scroll_label(ui: &mut egui::Ui, text: &str, max_width: f32, should_scroll: bo
     }
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
-enum Language {
-    Chinese,
-    English,
+struct SmartEditor {
+    // Name of the playlist being edited, None for a new one
+    original_name: Option<String>,
+    name: String,
+    playlist: SmartPlaylist,
+    limit: String,
 }
 
-impl Language {
-    fn as_str(&self) -> &'static str {
+// Playlist view filter next to the search box
+#[derive(Clone, Copy, PartialEq)]
+enum RatingFilter {
+    All,
+    Favourites,
+    AtLeast(u8),
+}
+
+impl RatingFilter {
+    fn matches(&self, meta: Option<&TrackMeta>) -> bool {
         match self {
-            Language::Chinese => "中文",
-            Language::English => "English",
+            RatingFilter::All => true,
+            RatingFilter::Favourites => meta.is_some_and(|meta| meta.favourite),
+            RatingFilter::AtLeast(stars) => meta.is_some_and(|meta| meta.rating >= *stars),
+        }
+    }
+
+    fn label(&self, language: Language) -> String {
+        match self {
+            RatingFilter::All => language.tr("all").to_string(),
+            RatingFilter::Favourites => "♥".to_string(),
+            RatingFilter::AtLeast(stars) => format!("{}+", "★".repeat(*stars as usize)),
+        }
+    }
+}
+
+// Context menu actions on the selected playlist rows
+enum BulkAction {
+    Remove,
+    MoveTo(String),
+    CopyTo(String),
+    Queue,
+    Reveal,
+    Rate(u8),
+    Favourite(bool),
+    EditTags,
+}
+
+struct TagEditor {
+    paths: Vec<PathBuf>,
+    // Values as read; edits are written only for fields that differ from these
+    original: TagFields,
+    fields: TagFields,
+    // Fields whose values differ between the selected files
+    mixed: Vec<&'static str>,
+    // Mixed fields the user chose to clear in every file
+    cleared: Vec<&'static str>,
+    cover: Option<CoverInfo>,
+    cover_change: Option<CoverChange>,
+}
+
+impl TagEditor {
+    fn open(paths: Vec<PathBuf>) -> Result<Self, String> {
+        let mut all = Vec::new();
+        let mut cover = None;
+        for path in &paths {
+            let (fields, file_cover) = tags::read_tags(path).map_err(|e| format!("{:?}: {}", path, e))?;
+            all.push(fields);
+            cover = cover.or(file_cover);
+        }
+        let (original, mixed) = tags::common_fields(&all);
+        Ok(Self { paths, fields: original.clone(), original, mixed, cleared: Vec::new(), cover, cover_change: None })
+    }
+}
+
+// A line in the lyrics timing editor; `time` is None until it's stamped
+struct EditorLine {
+    time: Option<Duration>,
+    text: String,
+    translation: Option<String>,
+    // Word timings are kept and moved along with the line
+    words: Vec<lrc::LyricWord>,
+}
+
+impl EditorLine {
+    fn set_time(&mut self, time: Duration) {
+        if let Some(old) = self.time {
+            for word in &mut self.words {
+                word.time = (word.time + time).saturating_sub(old);
+            }
+        } else {
+            // Words without a line time can't be placed
+            self.words.clear();
+        }
+        self.time = Some(time);
+    }
+}
+
+// How far the −/+ buttons move a line
+const LYRICS_NUDGE: Duration = Duration::from_millis(100);
+
+struct LyricsEditor {
+    // The track the lyrics belong to; saved as its sibling .lrc
+    audio_path: PathBuf,
+    metadata: LrcMetadata,
+    lines: Vec<EditorLine>,
+    // Next line to stamp
+    cursor: usize,
+    // Milliseconds to shift every stamped line by; positive moves them later
+    offset_ms: i64,
+    // Scroll the cursor line into view on the next frame
+    scroll_to_cursor: bool,
+    // What each stamp replaced, most recent last, for undo
+    stamps: Vec<StampUndo>,
+}
+
+// A line's timing before it was stamped, and the cursor at the time
+struct StampUndo {
+    line: usize,
+    time: Option<Duration>,
+    words: Vec<lrc::LyricWord>,
+    cursor: usize,
+}
+
+impl LyricsEditor {
+    /// Starts from the track's own lyrics text (LRC or plain), without any
+    /// separate translation file merged in.
+    fn new(audio_path: PathBuf, content: &str) -> Self {
+        let mut editor = Self {
+            audio_path,
+            metadata: LrcMetadata::default(),
+            lines: Vec::new(),
+            cursor: 0,
+            offset_ms: 0,
+            scroll_to_cursor: false,
+            stamps: Vec::new(),
+        };
+        editor.load_text(content);
+        editor
+    }
+
+    fn load(&mut self, lyrics: &Lyrics) {
+        self.metadata = lyrics.metadata.clone();
+        self.lines = lyrics.lines.iter()
+            .map(|line| EditorLine {
+                time: Some(line.time),
+                text: line.text.clone(),
+                translation: line.translation.clone(),
+                words: line.words.clone(),
+            })
+            .collect();
+        self.cursor = 0;
+        self.stamps.clear();
+    }
+
+    /// Loads LRC, or plain text with one unstamped line per non-empty line.
+    fn load_text(&mut self, content: &str) {
+        let lyrics = parse_lrc(content);
+        if !lyrics.is_empty() {
+            self.load(&lyrics);
+            return;
+        }
+        self.metadata = LrcMetadata::default();
+        self.lines = content.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| EditorLine { time: None, text: line.to_string(), translation: None, words: Vec::new() })
+            .collect();
+        self.cursor = 0;
+        self.stamps.clear();
+    }
+
+    fn stamp(&mut self, position: Duration) {
+        if let Some(line) = self.lines.get_mut(self.cursor) {
+            self.stamps.push(StampUndo { line: self.cursor, time: line.time, words: line.words.clone(), cursor: self.cursor });
+            line.set_time(position);
+            self.cursor += 1;
+            self.scroll_to_cursor = true;
+        }
+    }
+
+    /// Puts back the line timing the last stamp replaced and moves the cursor
+    /// back to it.
+    fn undo_stamp(&mut self) {
+        let Some(undo) = self.stamps.pop() else { return };
+        if let Some(line) = self.lines.get_mut(undo.line) {
+            line.time = undo.time;
+            line.words = undo.words;
+        }
+        self.cursor = undo.cursor;
+        self.scroll_to_cursor = true;
+    }
+
+    fn apply_offset(&mut self) {
+        let shift = Duration::from_millis(self.offset_ms.unsigned_abs());
+        for line in &mut self.lines {
+            if let Some(time) = line.time {
+                line.set_time(if self.offset_ms > 0 { time + shift } else { time.saturating_sub(shift) });
+            }
+        }
+        self.offset_ms = 0;
+    }
+
+    /// The stamped lines, sorted by time. Unstamped lines are left out.
+    fn to_lyrics(&self) -> Lyrics {
+        let mut lines: Vec<LyricLine> = self.lines.iter()
+            .filter_map(|line| Some(LyricLine {
+                time: line.time?,
+                text: line.text.trim().to_string(),
+                words: line.words.clone(),
+                translation: line.translation.clone(),
+            }))
+            .collect();
+        lines.sort_by_key(|line| line.time);
+        Lyrics { metadata: LrcMetadata { offset_ms: 0, ..self.metadata.clone() }, lines }
+    }
+}
+
+// The track currently being listened to, for the history
+struct ListenSession {
+    path: PathBuf,
+    started: u64,
+    listened: Duration,
+    last_position: Duration,
+    duration: Duration,
+}
+
+impl ListenSession {
+    // Less than half the track (or four minutes) before moving on counts as a skip
+    fn is_skip(&self, finished: bool) -> bool {
+        if finished {
+            return false;
+        }
+        let threshold = if self.duration.is_zero() {
+            Duration::from_secs(30)
+        } else {
+            (self.duration / 2).min(Duration::from_secs(240))
+        };
+        self.listened < threshold
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+enum LyricsAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl LyricsAlign {
+    const ALL: [LyricsAlign; 3] = [LyricsAlign::Left, LyricsAlign::Center, LyricsAlign::Right];
+
+    fn as_str(&self, lang: Language) -> &'static str {
+        lang.tr(match self {
+            LyricsAlign::Left => "align.left",
+            LyricsAlign::Center => "align.center",
+            LyricsAlign::Right => "align.right",
+        })
+    }
+
+    fn to_align(self) -> egui::Align {
+        match self {
+            LyricsAlign::Left => egui::Align::LEFT,
+            LyricsAlign::Center => egui::Align::Center,
+            LyricsAlign::Right => egui::Align::RIGHT,
+        }
+    }
+}
+
+// How desktop lyrics are drawn, besides font size and colour
+#[derive(Clone, Copy)]
+struct TextEffects {
+    align: LyricsAlign,
+    outline: Option<egui::Color32>,
+    shadow: Option<egui::Color32>,
+}
+
+/// Paints a lyric line in `rect`, wrapping if needed. With `highlight`, the
+/// first `chars` characters (fractional for the word being sung) are drawn in
+/// the highlight colour on top, karaoke style.
+fn paint_lyric_line(ui: &egui::Ui, rect: egui::Rect, text: &str, font_size: f32, color: egui::Color32, highlight: Option<(f32, egui::Color32)>, effects: TextEffects) {
+    let mut job = egui::text::LayoutJob::simple(text.to_string(), egui::FontId::new(font_size, fonts::lyrics_family()), color, rect.width());
+    job.halign = effects.align.to_align();
+    let galley = ui.painter().layout_job(job);
+    // Aligned rows are laid out relative to x = 0: starting, centred or ending there
+    let x = match effects.align {
+        LyricsAlign::Left => rect.left(),
+        LyricsAlign::Center => rect.center().x,
+        LyricsAlign::Right => rect.right(),
+    };
+    let pos = egui::pos2(x, rect.center().y - galley.size().y / 2.0);
+
+    // Shadow and outline scale with the text so they read the same at any size
+    let width = (font_size / 24.0).max(1.0);
+    if let Some(shadow) = effects.shadow {
+        ui.painter().galley_with_override_text_color(pos + egui::vec2(width, width) * 1.5, galley.clone(), shadow);
+    }
+    if let Some(outline) = effects.outline {
+        for (dx, dy) in [(-1.0, -1.0), (0.0, -1.0), (1.0, -1.0), (-1.0, 0.0), (1.0, 0.0), (-1.0, 1.0), (0.0, 1.0), (1.0, 1.0)] {
+            ui.painter().galley_with_override_text_color(pos + egui::vec2(dx, dy) * width, galley.clone(), outline);
         }
     }
+    ui.painter().galley(pos, galley.clone(), color);
+
+    let Some((chars, highlight_color)) = highlight else { return };
+    if chars <= 0.0 {
+        return;
+    }
+    let index = chars.floor() as usize;
+    let at = galley.pos_from_cursor(egui::text::CCursor::new(index));
+    let next = galley.pos_from_cursor(egui::text::CCursor::new(index + 1));
+    let x = if next.min.y == at.min.y { egui::lerp(at.min.x..=next.min.x, chars.fract()) } else { at.min.x };
+
+    // Rows above the current one in full, then the current row up to `x`
+    let done_rows = egui::Rect::from_min_max(egui::pos2(rect.min.x, pos.y), egui::pos2(rect.max.x, pos.y + at.min.y));
+    let current_row = egui::Rect::from_min_max(egui::pos2(rect.min.x, pos.y + at.min.y), egui::pos2(pos.x + x, pos.y + at.max.y));
+    for clip in [done_rows, current_row] {
+        ui.painter().with_clip_rect(clip.intersect(rect)).galley_with_override_text_color(pos, galley.clone(), highlight_color);
+    }
+}
+
+/// Combo box for a font: `auto_label` (no font chosen), the system fonts, or a
+/// file picked with "Browse…".
+fn font_picker(ui: &mut egui::Ui, id: &str, font: &mut Option<FontChoice>, system_fonts: &[FontChoice], auto_label: &str, lang: Language) {
+    ui.horizontal(|ui| {
+        let selected = font.as_ref().map_or(auto_label, |f| f.name.as_str());
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(selected)
+            .width(200.0)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(font, None, auto_label);
+                for choice in system_fonts {
+                    ui.selectable_value(font, Some(choice.clone()), &choice.name);
+                }
+            });
+        if ui.button(lang.tr("browse")).clicked() {
+            if let Some(path) = rfd::FileDialog::new().add_filter("Font", &["ttf", "otf", "ttc", "otc"]).pick_file() {
+                *font = Some(FontChoice::from_file(path));
+            }
+        }
+    });
 }
 
 #[derive(Clone, Debug)]
@@ -650,122 +2575,994 @@ enum PlayerStatus {
     Paused,
 }
 
-struct LyricsState {
-    current_lyric: String,
-    show_desktop_lyrics: bool,
-    font_size: f32,
-    text_color: egui::Color32,
-    bg_color: egui::Color32,
-}
+// How long the lyrics panel stops following playback after a manual scroll
+const LYRICS_FOLLOW_PAUSE: Duration = Duration::from_secs(4);
+
+struct LyricsState {
+    current_lyric: String,
+    // Karaoke progress through `current_lyric` in characters, for lines with word timings
+    highlight_chars: Option<f32>,
+    current_translation: String,
+    show_desktop_lyrics: bool,
+    font_size: f32,
+    text_color: egui::Color32,
+    highlight_color: egui::Color32,
+    translation_font_size: f32,
+    translation_color: egui::Color32,
+    bg_color: egui::Color32,
+
+    // Desktop lyrics window
+    next_lyric: String,
+    locked: bool,
+    two_lines: bool,
+    align: LyricsAlign,
+    outline: bool,
+    shadow: bool,
+    effect_color: egui::Color32,
+    // Last known geometry; saved shortly after the window stops moving
+    window_pos: Option<egui::Pos2>,
+    window_size: egui::Vec2,
+    window_changed_at: Option<Instant>,
+}
+
+impl LyricsState {
+    // Translation size relative to the lyric itself, so the main window and
+    // the lyrics panel follow the desktop lyrics setting at their own sizes
+    fn translation_scale(&self) -> f32 {
+        if self.font_size > 0.0 { self.translation_font_size / self.font_size } else { 1.0 }
+    }
+
+    // Copies the display settings into the config
+    fn store(&self, config: &mut AppConfig) {
+        config.lyrics_font_size = self.font_size;
+        config.lyrics_text_color = self.text_color.to_array();
+        config.lyrics_highlight_color = self.highlight_color.to_array();
+        config.lyrics_translation_font_size = self.translation_font_size;
+        config.lyrics_translation_color = self.translation_color.to_array();
+        config.lyrics_bg_color = self.bg_color.to_array();
+        config.desktop_lyrics_pos = self.window_pos.map(|pos| [pos.x, pos.y]);
+        config.desktop_lyrics_size = [self.window_size.x, self.window_size.y];
+        config.desktop_lyrics_locked = self.locked;
+        config.desktop_lyrics_two_lines = self.two_lines;
+        config.desktop_lyrics_align = self.align;
+        config.desktop_lyrics_outline = self.outline;
+        config.desktop_lyrics_shadow = self.shadow;
+        config.desktop_lyrics_effect_color = self.effect_color.to_array();
+    }
+
+    fn effects(&self) -> TextEffects {
+        TextEffects {
+            align: self.align,
+            outline: self.outline.then_some(self.effect_color),
+            shadow: self.shadow.then_some(self.effect_color),
+        }
+    }
+}
+
+struct MusicPlayerApp {
+    audio_tx: Sender<AudioCommand>,
+    audio_rx: Receiver<AudioStatus>,
+    data: Arc<Mutex<PlaylistsManager>>,
+    now_playing: Arc<Mutex<NowPlaying>>,
+    volume: f32,
+    player_status: PlayerStatus,
+    api_port: u16,
+    port_tx: mpsc::UnboundedSender<u16>,
+    port_input: String,
+    new_playlist_name: String,
+    current_playing_file: Option<PathBuf>,
+    
+    // Lyrics
+    lyrics: Lyrics,
+    lyrics_state: Arc<Mutex<LyricsState>>,
+    show_lyrics_settings: bool,
+    show_lyrics_panel: bool,
+    // Geometry the desktop lyrics window was (re)opened with. Kept fixed while
+    // it's open, since changing the builder would move the window.
+    desktop_lyrics_geometry: (Option<egui::Pos2>, egui::Vec2),
+    desktop_lyrics_was_shown: bool,
+    // Auto-follow in the lyrics panel is paused until then after a manual scroll
+    lyrics_follow_paused_until: Option<Instant>,
+    lyrics_sources: Vec<LyricsSource>,
+    lyrics_dirs: Vec<PathBuf>,
+    
+    // UI Settings
+    theme: Theme,
+    themes: Vec<Theme>,
+    write_rating_tags: Arc<AtomicBool>,
+    lyrics_lookup: Arc<Mutex<LyricsLookup>>,
+    ui_font: Option<FontChoice>,
+    lyrics_font: Option<FontChoice>,
+    // Listed when the settings window is first opened
+    system_fonts: Option<Vec<FontChoice>>,
+
+    // Playback State
+    playback_mode: PlaybackMode,
+    current_position: Duration,
+    total_duration: Duration,
+    is_playing: bool,
+    is_seeking: bool, // To prevent updates while dragging slider
+    seek_target: Option<Duration>, // For optimistic updates
+    last_sync_time: Option<Instant>, // For interpolation
+
+    // Duplicate Handling
+    show_duplicate_dialog: bool,
+    pending_files: Vec<PathBuf>,
+    // Audio files found in dropped folders, walked on a worker thread
+    dropped_tx: Sender<Vec<PathBuf>>,
+    dropped_rx: Receiver<Vec<PathBuf>>,
+    // Failures from tag edits saved on a worker thread
+    tag_errors_tx: Sender<Vec<String>>,
+    tag_errors_rx: Receiver<Vec<String>>,
+    // Lyrics of a newly started track, looked up on a worker thread
+    lyrics_tx: Sender<(PathBuf, Lyrics)>,
+    lyrics_rx: Receiver<(PathBuf, Lyrics)>,
+
+    // Search
+    search_query: String,
+    search_cursor: usize,
+    rating_filter: RatingFilter,
+
+    // Sorting
+    last_sort: Option<(String, PlaylistSort)>,
+
+    // Selection, by item rather than index so it survives reordering and sorting
+    selected: HashSet<PlaylistItem>,
+    select_anchor: Option<PlaylistItem>,
+    selection_list: String,
+    // Where the list continues once the queue is empty
+    queue_resume: Option<PathBuf>,
+
+    // Errors to show the user (failed import/export etc.)
+    error_message: Option<String>,
+
+    // Smart playlist editor, open while Some
+    smart_editor: Option<SmartEditor>,
+    tag_editor: Option<TagEditor>,
+    lyrics_editor: Option<LyricsEditor>,
+    listening: Option<ListenSession>,
+
+    // Playlist Management Dialogs
+    show_rename_dialog: bool,
+    rename_playlist_name: String,
+    show_delete_playlist_dialog: bool,
+    playlist_to_delete: Option<String>,
+
+    // Language
+    language: Language,
+
+    // Keyboard shortcuts and media keys
+    shortcuts: Bindings,
+    // Action whose shortcut is being recorded in the settings
+    capturing_shortcut: Option<Action>,
+    media_rx: Receiver<MediaRequest>,
+    // Published to and controlled by the desktop over D-Bus
+    #[cfg(target_os = "linux")]
+    mpris: Option<mpris::Mpris>,
+}
+
+impl MusicPlayerApp {
+    fn new(state: AppState, audio_rx: Receiver<AudioStatus>, port: u16, port_tx: mpsc::UnboundedSender<u16>, load_errors: Vec<String>, cc: &eframe::CreationContext<'_>) -> Self {
+        let AppState { audio_tx, data, now_playing, write_rating_tags, lyrics_lookup } = state;
+        // Load Config for Lyrics
+        let config = AppConfig::load();
+        
+        // Fonts, including one with Chinese glyphs
+        let mut load_errors = load_errors;
+        load_errors.extend(fonts::install(&cc.egui_ctx, config.ui_font.as_ref(), config.lyrics_font.as_ref()));
+
+        let (dropped_tx, dropped_rx) = unbounded();
+        let (lyrics_tx, lyrics_rx) = unbounded();
+        let (tag_errors_tx, tag_errors_rx) = unbounded();
+
+        // Media keys and desktop media controls arrive through MPRIS on Linux
+        let (media_tx, media_rx) = unbounded();
+        #[cfg(target_os = "linux")]
+        let mpris = mpris::Mpris::start(media_tx, cc.egui_ctx.clone())
+            .map_err(|e| eprintln!("MPRIS unavailable, media keys won't work: {}", e))
+            .ok();
+        #[cfg(not(target_os = "linux"))]
+        drop(media_tx);
+
+        let lyrics_state = Arc::new(Mutex::new(LyricsState {
+            current_lyric: "".to_string(),
+            highlight_chars: None,
+            current_translation: "".to_string(),
+            show_desktop_lyrics: false,
+            font_size: config.lyrics_font_size,
+            text_color: color(config.lyrics_text_color),
+            highlight_color: color(config.lyrics_highlight_color),
+            translation_font_size: config.lyrics_translation_font_size,
+            translation_color: color(config.lyrics_translation_color),
+            next_lyric: "".to_string(),
+            locked: config.desktop_lyrics_locked,
+            two_lines: config.desktop_lyrics_two_lines,
+            align: config.desktop_lyrics_align,
+            outline: config.desktop_lyrics_outline,
+            shadow: config.desktop_lyrics_shadow,
+            effect_color: color(config.desktop_lyrics_effect_color),
+            window_pos: config.desktop_lyrics_pos.map(|[x, y]| egui::pos2(x, y)),
+            window_size: egui::vec2(config.desktop_lyrics_size[0], config.desktop_lyrics_size[1]),
+            window_changed_at: None,
+            bg_color: color(config.lyrics_bg_color),
+        }));
+
+        Self {
+            audio_tx,
+            audio_rx,
+            data,
+            now_playing,
+            volume: 1.0,
+            player_status: PlayerStatus::Ready,
+            api_port: port,
+            port_tx,
+            port_input: port.to_string(),
+            new_playlist_name: "".to_string(),
+            current_playing_file: None,
+            lyrics: Lyrics::default(),
+            lyrics_state,
+            show_lyrics_settings: false,
+            show_lyrics_panel: false,
+            desktop_lyrics_geometry: (None, egui::Vec2::ZERO),
+            desktop_lyrics_was_shown: false,
+            lyrics_follow_paused_until: None,
+            lyrics_sources: config.lyrics_sources.clone(),
+            lyrics_dirs: config.lyrics_dirs.clone(),
+            theme: config.theme.clone(),
+            themes: config.themes.clone(),
+            write_rating_tags,
+            lyrics_lookup,
+            ui_font: config.ui_font.clone(),
+            lyrics_font: config.lyrics_font.clone(),
+            system_fonts: None,
+            playback_mode: PlaybackMode::Order,
+            current_position: Duration::from_secs(0),
+            total_duration: Duration::from_secs(0),
+            is_playing: false,
+            is_seeking: false,
+            seek_target: None,
+            last_sync_time: None,
+            show_duplicate_dialog: false,
+            pending_files: Vec::new(),
+            dropped_tx,
+            dropped_rx,
+            lyrics_tx,
+            lyrics_rx,
+            tag_errors_tx,
+            tag_errors_rx,
+            search_query: "".to_string(),
+            search_cursor: 0,
+            rating_filter: RatingFilter::All,
+            last_sort: None,
+            selected: HashSet::new(),
+            select_anchor: None,
+            selection_list: "".to_string(),
+            queue_resume: None,
+            // Problems reading config.json / playlists.json at startup
+            error_message: if load_errors.is_empty() { None } else { Some(load_errors.join("\n\n")) },
+            smart_editor: None,
+            tag_editor: None,
+            lyrics_editor: None,
+            listening: None,
+            show_rename_dialog: false,
+            rename_playlist_name: "".to_string(),
+            show_delete_playlist_dialog: false,
+            playlist_to_delete: None,
+            language: Language::from_code(&config.language).unwrap_or_default(),
+            shortcuts: config.shortcuts.clone(),
+            capturing_shortcut: None,
+            media_rx,
+            #[cfg(target_os = "linux")]
+            mpris,
+        }
+    }
+
+    /// Ends the current listen and logs it. `finished` is true when the track
+    /// played to the end rather than being stopped or changed.
+    fn finish_listening(&mut self, finished: bool) {
+        if let Some(session) = self.listening.take() {
+            let skipped = session.is_skip(finished);
+            let known = self.data.lock().unwrap().library.contains_key(&session.path);
+            let meta = (!known).then(|| read_track_meta(&session.path));
+            let mut data = self.data.lock().unwrap();
+            data.record_play(HistoryEntry {
+                path: session.path,
+                timestamp: session.started,
+                listened_secs: session.listened.as_secs(),
+                skipped,
+            }, meta);
+            data.save();
+        }
+    }
+
+    fn play_file(&mut self, path: PathBuf) {
+        let _ = self.audio_tx.send(AudioCommand::PlayFile(path.clone()));
+        self.current_playing_file = Some(path.clone());
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.player_status = PlayerStatus::Playing(file_name.to_string());
+        self.is_playing = true;
+        self.last_sync_time = Some(Instant::now());
+        self.current_position = Duration::from_secs(0);
+    }
+
+    /// The playback position, interpolated since the last status update.
+    fn display_position(&self) -> Duration {
+        let mut position = self.current_position;
+        if self.is_playing && !self.is_seeking && self.seek_target.is_none() {
+            if let Some(last_time) = self.last_sync_time {
+                position += last_time.elapsed();
+                if position > self.total_duration {
+                    position = self.total_duration;
+                }
+            }
+        }
+        position
+    }
+
+    fn pause(&mut self) {
+        let _ = self.audio_tx.send(AudioCommand::Pause);
+        self.is_playing = false; // Immediate feedback
+        self.player_status = PlayerStatus::Paused;
+        self.last_sync_time = None;
+    }
+
+    /// Resumes the current track, or starts the list if nothing was playing.
+    fn resume(&mut self) {
+        if let Some(path) = &self.current_playing_file {
+            let _ = self.audio_tx.send(AudioCommand::Resume);
+            self.is_playing = true;
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            self.player_status = PlayerStatus::Playing(name);
+            self.last_sync_time = Some(Instant::now());
+        } else {
+            // Try play first in list
+            self.play_next();
+        }
+    }
+
+    fn stop(&mut self) {
+        let _ = self.audio_tx.send(AudioCommand::Stop);
+        self.finish_listening(false);
+        self.current_position = Duration::from_secs(0);
+        self.is_playing = false;
+        self.player_status = PlayerStatus::Stopped;
+        self.last_sync_time = None;
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        let _ = self.audio_tx.send(AudioCommand::SetVolume(self.volume));
+    }
+
+    fn seek(&mut self, target: Duration) {
+        let _ = self.audio_tx.send(AudioCommand::Seek(target));
+        self.seek_target = Some(target);
+        self.current_position = target;
+        // Don't enable interpolation yet, wait for sync
+        self.last_sync_time = None;
+    }
+
+    /// Adds dropped files to the top of the current playlist. Files whose name is
+    /// already taken are held back in `pending_files` and go through the
+    /// duplicate dialog.
+    fn add_files_to_current(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        let mut data = self.data.lock().unwrap();
+        let current_name = data.current_name.clone();
+        if data.is_smart(&current_name) {
+            self.error_message = Some(self.language.tr("smart_playlist_no_add").to_string());
+            return;
+        }
+        let list = data.lists.entry(current_name.clone()).or_default();
+        
+        let mut duplicates = Vec::new();
+        let mut non_duplicates: Vec<PlaylistItem> = Vec::new();
+        
+        for path in paths {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if list.iter().chain(non_duplicates.iter()).any(|item| item.name == name) {
+                duplicates.push(path);
+            } else {
+                non_duplicates.push(PlaylistItem { path, name });
+            }
+        }
+        
+        // Add non-duplicates immediately, keeping their order
+        list.splice(0..0, non_duplicates);
+        data.apply_sort(&current_name);
+        
+        if !duplicates.is_empty() {
+            self.pending_files.extend(duplicates);
+            self.show_duplicate_dialog = true;
+        }
+        
+        data.save();
+        drop(data);
+        spawn_library_indexer(self.data.clone());
+    }
+
+    fn show_smart_editor(&mut self, ctx: &egui::Context) {
+        let lang = self.language;
+        let title = lang.tr("smart_playlist");
+        let mut open = true;
+        let mut should_close = false;
+        let mut save = false;
+        let Some(editor) = self.smart_editor.as_mut() else { return };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(lang.tr("name"));
+                    ui.text_edit_singleline(&mut editor.name);
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut editor.playlist.match_all, true, lang.tr("match_all_rules"));
+                    ui.radio_value(&mut editor.playlist.match_all, false, lang.tr("match_any_rule"));
+                });
+
+                let mut rule_to_remove = None;
+                egui::Grid::new("smart_rules_grid").num_columns(4).show(ui, |ui| {
+                    for (i, rule) in editor.playlist.rules.iter_mut().enumerate() {
+                        egui::ComboBox::from_id_salt(("smart_field", i))
+                            .selected_text(rule.field.as_str(lang))
+                            .show_ui(ui, |ui| {
+                                for field in SMART_FIELDS {
+                                    ui.selectable_value(&mut rule.field, field, field.as_str(lang));
+                                }
+                            });
+                        // Keep the operator valid for the field type
+                        let ops = if rule.field.is_numeric() { NUMERIC_OPS } else { TEXT_OPS };
+                        if !ops.contains(&rule.op) {
+                            rule.op = ops[0];
+                        }
+                        egui::ComboBox::from_id_salt(("smart_op", i))
+                            .selected_text(rule.op.as_str(lang))
+                            .show_ui(ui, |ui| {
+                                for op in ops {
+                                    ui.selectable_value(&mut rule.op, op, op.as_str(lang));
+                                }
+                            });
+                        ui.text_edit_singleline(&mut rule.value);
+                        if ui.button("🗑").clicked() {
+                            rule_to_remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(i) = rule_to_remove {
+                    editor.playlist.rules.remove(i);
+                }
+                if ui.button(lang.tr("add_rule")).clicked() {
+                    editor.playlist.rules.push(SmartRule {
+                        field: SmartField::Genre,
+                        op: SmartOp::Contains,
+                        value: String::new(),
+                    });
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(lang.tr("sort_order"));
+                    egui::ComboBox::from_id_salt("smart_sort")
+                        .selected_text(editor.playlist.sort.as_str(lang))
+                        .show_ui(ui, |ui| {
+                            for sort in SMART_SORTS {
+                                ui.selectable_value(&mut editor.playlist.sort, sort, sort.as_str(lang));
+                            }
+                        });
+                    ui.label(lang.tr("limit"));
+                    ui.add(egui::TextEdit::singleline(&mut editor.limit).desired_width(50.0));
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button(lang.tr("save")).clicked() {
+                        save = true;
+                    }
+                    if ui.button(lang.tr("cancel")).clicked() {
+                        should_close = true;
+                    }
+                });
+            });
+
+        if save {
+            let name = editor.name.trim().to_string();
+            let mut data = self.data.lock().unwrap();
+            let renamed = editor.original_name.as_deref() != Some(name.as_str());
+            if name.is_empty() || (renamed && data.lists.contains_key(&name)) {
+                self.error_message = Some(lang.tr("playlist_name_taken").to_string());
+            } else {
+                editor.playlist.limit = editor.limit.trim().parse().ok();
+                if let Some(old_name) = editor.original_name.as_ref().filter(|_| renamed) {
+                    data.smart.remove(old_name);
+                    data.lists.remove(old_name);
+                }
+                data.smart.insert(name.clone(), editor.playlist.clone());
+                data.refresh_smart_playlists();
+                data.current_name = name;
+                data.save();
+                should_close = true;
+            }
+        }
+        if !open || should_close {
+            self.smart_editor = None;
+        }
+    }
+
+    fn show_tag_editor(&mut self, ctx: &egui::Context) {
+        let lang = self.language;
+        let Some(editor) = self.tag_editor.as_mut() else { return };
+        let title = match editor.paths.len() {
+            1 => lang.tr("edit_tags_title").to_string(),
+            n => lang.trf("edit_tags_title_files", &[("n", &n)]),
+        };
+        let mixed_hint = lang.tr("multiple_values");
+        let cleared_hint = lang.tr("cleared_on_save");
+        let mut open = true;
+        let mut should_close = false;
+        let mut save = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(true)
+            .default_width(420.0)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if let [path] = editor.paths.as_slice() {
+                    ui.label(egui::RichText::new(path.to_string_lossy()).weak());
+                }
+                egui::Grid::new("tag_editor_grid").num_columns(2).show(ui, |ui| {
+                    let fields: [(&'static str, &'static str, &mut String); 8] = [
+                        ("title", "tag.title", &mut editor.fields.title),
+                        ("artist", "tag.artist", &mut editor.fields.artist),
+                        ("album", "tag.album", &mut editor.fields.album),
+                        ("album_artist", "tag.album_artist", &mut editor.fields.album_artist),
+                        ("genre", "tag.genre", &mut editor.fields.genre),
+                        ("year", "tag.year", &mut editor.fields.year),
+                        ("track", "tag.track", &mut editor.fields.track),
+                        ("comment", "tag.comment", &mut editor.fields.comment),
+                    ];
+                    for (key, label, value) in fields {
+                        ui.label(lang.tr(label));
+                        let mixed = editor.mixed.contains(&key);
+                        let cleared = editor.cleared.contains(&key);
+                        let hint = if cleared { cleared_hint } else if mixed { mixed_hint } else { "" };
+                        ui.horizontal(|ui| {
+                            // A mixed field shows up empty, so leaving it empty can't mean "clear"
+                            if mixed && !cleared && ui.button("✖").on_hover_text(lang.tr("clear_all_values")).clicked() {
+                                value.clear();
+                                editor.cleared.push(key);
+                            }
+                            ui.add(egui::TextEdit::singleline(value).hint_text(hint).desired_width(f32::INFINITY));
+                        });
+                        ui.end_row();
+                    }
+
+                    ui.label(lang.tr("cover"));
+                    ui.horizontal(|ui| {
+                        let status = match (&editor.cover_change, &editor.cover) {
+                            (Some(CoverChange::Replace(path)), _) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                            (Some(CoverChange::Remove), _) | (None, None) => lang.tr("none").to_string(),
+                            (None, Some(cover)) => format!("{} ({} KB)", cover.mime_type, cover.size / 1024),
+                        };
+                        ui.label(status);
+                        if ui.button(lang.tr("replace")).clicked() {
+                            if let Some(path) = rfd::FileDialog::new().add_filter("Image", &["jpg", "jpeg", "png", "gif", "bmp"]).pick_file() {
+                                editor.cover_change = Some(CoverChange::Replace(path));
+                            }
+                        }
+                        if ui.button(lang.tr("remove")).clicked() {
+                            editor.cover_change = Some(CoverChange::Remove);
+                        }
+                    });
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label(lang.tr("embedded_lyrics_hint"));
+                    // Embeds the .lrc next to the file as synced lyrics
+                    if let [path] = editor.paths.as_slice() {
+                        let lrc_path = path.with_extension("lrc");
+                        if lrc_path.exists() && ui.button(lang.tr("import_lrc")).clicked() {
+                            if let Ok(content) = std::fs::read_to_string(&lrc_path) {
+                                editor.fields.lyrics = content;
+                            }
+                        }
+                    }
+                });
+                let hint = if editor.cleared.contains(&"lyrics") {
+                    cleared_hint
+                } else if editor.mixed.contains(&"lyrics") {
+                    if ui.button(lang.tr("clear_all_values")).clicked() {
+                        editor.fields.lyrics.clear();
+                        editor.cleared.push("lyrics");
+                    }
+                    mixed_hint
+                } else {
+                    ""
+                };
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    ui.add(egui::TextEdit::multiline(&mut editor.fields.lyrics)
+                        .hint_text(hint)
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY));
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button(lang.tr("save")).clicked() {
+                        save = true;
+                    }
+                    if ui.button(lang.tr("cancel")).clicked() {
+                        should_close = true;
+                    }
+                });
+            });
+
+        if save {
+            let mut changes = TagChanges::between(&editor.original, &editor.fields);
+            for name in &editor.cleared {
+                changes.clear(name);
+            }
+            changes.cover = editor.cover_change.clone();
+            if !changes.is_empty() {
+                // A big selection takes a while to write; errors come back through tag_errors_rx
+                let (data, paths, tx, ctx) = (self.data.clone(), editor.paths.clone(), self.tag_errors_tx.clone(), ctx.clone());
+                thread::spawn(move || {
+                    let _ = tx.send(write_tags_and_reindex(&data, &paths, &changes));
+                    ctx.request_repaint();
+                });
+            }
+            should_close = true;
+        }
+        if !open || should_close {
+            self.tag_editor = None;
+        }
+    }
+
+    /// Timing editor for the playing track's lyrics. Space (or the Stamp button)
+    /// stamps the next line with the playback position.
+    fn show_lyrics_editor(&mut self, ctx: &egui::Context, position: Duration) {
+        let lang = self.language;
+        let Some(editor) = self.lyrics_editor.as_mut() else { return };
+        let mut open = true;
+        let mut should_close = false;
+        let mut save = false;
+        let mut seek_to = None;
+
+        // Space stamps unless a text field has the keyboard
+        if ctx.memory(|m| m.focused().is_none()) && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Space)) {
+            editor.stamp(position);
+        }
+
+        egui::Window::new(lang.tr("lyrics_timing_editor"))
+            .collapsible(false)
+            .resizable(true)
+            .default_size([520.0, 480.0])
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(editor.audio_path.to_string_lossy()).weak());
+                ui.horizontal(|ui| {
+                    if ui.button(lang.tr("load_file")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Lyrics", &["lrc", "txt"]).pick_file() {
+                            match std::fs::read(&path) {
+                                Ok(bytes) => editor.load_text(&String::from_utf8_lossy(&bytes)),
+                                Err(e) => self.error_message = Some(format!("{:?}: {}", path, e)),
+                            }
+                        }
+                    }
+                    if ui.button(lang.tr("clear_all_times")).clicked() {
+                        for line in &mut editor.lines {
+                            line.time = None;
+                            line.words.clear();
+                        }
+                        editor.cursor = 0;
+                        editor.stamps.clear();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}  {}", lang.tr("position"), format_timestamp(position)));
+                    if ui.add_enabled(editor.cursor < editor.lines.len(), egui::Button::new(lang.tr("stamp_space"))).clicked() {
+                        editor.stamp(position);
+                    }
+                    if ui.add_enabled(!editor.stamps.is_empty(), egui::Button::new(lang.tr("undo"))).clicked() {
+                        editor.undo_stamp();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label(lang.tr("shift_all_ms"));
+                    ui.add(egui::DragValue::new(&mut editor.offset_ms).speed(10.0));
+                    if ui.add_enabled(editor.offset_ms != 0, egui::Button::new(lang.tr("apply"))).clicked() {
+                        editor.apply_offset();
+                    }
+                });
+                ui.separator();
+
+                let scroll_to_cursor = std::mem::take(&mut editor.scroll_to_cursor);
+                egui::ScrollArea::vertical().max_height(ui.available_height() - 40.0).auto_shrink([false, true]).show(ui, |ui| {
+                    egui::Grid::new("lyrics_editor_grid").num_columns(5).striped(true).show(ui, |ui| {
+                        for (i, line) in editor.lines.iter_mut().enumerate() {
+                            // The marker selects the next line to stamp
+                            let marker = ui.selectable_label(i == editor.cursor, if i == editor.cursor { "▶" } else { " " });
+                            if marker.clicked() {
+                                editor.cursor = i;
+                            }
+                            if scroll_to_cursor && i == editor.cursor {
+                                marker.scroll_to_me(Some(egui::Align::Center));
+                            }
+                            match line.time {
+                                Some(time) => {
+                                    if ui.link(format_timestamp(time)).on_hover_text(lang.tr("seek_here")).clicked() {
+                                        seek_to = Some(time);
+                                    }
+                                }
+                                None => {
+                                    ui.weak("--:--.--");
+                                }
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(line.time.is_some(), egui::Button::new("−").small()).clicked() {
+                                    if let Some(time) = line.time {
+                                        line.set_time(time.saturating_sub(LYRICS_NUDGE));
+                                    }
+                                }
+                                if ui.add_enabled(line.time.is_some(), egui::Button::new("+").small()).clicked() {
+                                    if let Some(time) = line.time {
+                                        line.set_time(time + LYRICS_NUDGE);
+                                    }
+                                }
+                            });
+                            if ui.add(egui::TextEdit::singleline(&mut line.text).desired_width(260.0)).changed() {
+                                // The word timings no longer match the text
+                                line.words.clear();
+                            }
+                            match &line.translation {
+                                Some(translation) => ui.weak(translation),
+                                None => ui.label(""),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                let unstamped = editor.lines.iter().filter(|line| line.time.is_none()).count();
+                if unstamped > 0 {
+                    ui.weak(lang.trf("unstamped_lines", &[("n", &unstamped)]));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(lang.tr("save_as_lrc")).clicked() {
+                        save = true;
+                    }
+                    if ui.button(lang.tr("close")).clicked() {
+                        should_close = true;
+                    }
+                });
+            });
+
+        if save {
+            let lyrics = editor.to_lyrics();
+            let lrc_path = editor.audio_path.with_extension("lrc");
+            match storage::replace_file(&lrc_path, lyrics.to_lrc().as_bytes()) {
+                Ok(()) => {
+                    // Show the new timings straight away if the track is still playing
+                    if self.current_playing_file.as_ref() == Some(&editor.audio_path) {
+                        let lyrics = lyrics::parse_with_translation(&lyrics.to_lrc(), &editor.audio_path, &self.lyrics_dirs);
+                        self.now_playing.lock().unwrap().lyrics = lyrics.clone();
+                        self.lyrics = lyrics;
+                    }
+                    should_close = true;
+                }
+                Err(e) => self.error_message = Some(format!("{:?}: {}", lrc_path, e)),
+            }
+        }
+        if let Some(target) = seek_to {
+            self.seek(target);
+        }
+        if !open || should_close {
+            self.lyrics_editor = None;
+        }
+    }
+
+    /// Theme section of the settings window: presets, colours, sizes and
+    /// JSON import/export.
+    fn show_theme_settings(&mut self, ui: &mut egui::Ui) {
+        let lang = self.language;
+        egui::Grid::new("theme_grid").num_columns(2).show(ui, |ui| {
+            ui.label(lang.tr("theme_preset"));
+            egui::ComboBox::from_id_salt("theme_preset")
+                .selected_text(&self.theme.name)
+                .show_ui(ui, |ui| {
+                    for preset in Theme::built_in().into_iter().chain(self.themes.iter().cloned()) {
+                        if ui.selectable_label(self.theme == preset, &preset.name).clicked() {
+                            self.theme = preset;
+                        }
+                    }
+                });
+            ui.end_row();
+
+            ui.label(lang.tr("theme_base"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.theme.base, ThemeBase::Dark, lang.tr("theme_dark"));
+                ui.radio_value(&mut self.theme.base, ThemeBase::Light, lang.tr("theme_light"));
+            });
+            ui.end_row();
 
-struct MusicPlayerApp {
-    audio_tx: Sender<AudioCommand>,
-    audio_rx: Receiver<AudioStatus>,
-    data: Arc<Mutex<PlaylistsManager>>,
-    volume: f32,
-    player_status: PlayerStatus,
-    api_port: u16,
-    port_tx: mpsc::UnboundedSender<u16>,
-    port_input: String,
-    new_playlist_name: String,
-    current_playing_file: Option<PathBuf>,
-    
-    // Lyrics
-    lyrics: Vec<(Duration, String)>,
-    lyrics_state: Arc<Mutex<LyricsState>>,
-    show_lyrics_settings: bool,
-    
-    // UI Settings
-    ui_bg_color: egui::Color32,
+            ui.label(lang.tr("theme_background"));
+            ui.color_edit_button_srgba_unmultiplied(&mut self.theme.background);
+            ui.end_row();
 
-    // Playback State
-    playback_mode: PlaybackMode,
-    current_position: Duration,
-    total_duration: Duration,
-    is_playing: bool,
-    is_seeking: bool, // To prevent updates while dragging slider
-    seek_target: Option<Duration>, // For optimistic updates
-    last_sync_time: Option<Instant>, // For interpolation
+            ui.label(lang.tr("theme_accent"));
+            ui.color_edit_button_srgba_unmultiplied(&mut self.theme.accent);
+            ui.end_row();
 
-    // Duplicate Handling
-    show_duplicate_dialog: bool,
-    pending_files: Vec<PathBuf>,
+            ui.label(lang.tr("theme_text"));
+            ui.color_edit_button_srgba_unmultiplied(&mut self.theme.text);
+            ui.end_row();
 
-    // Playlist Management Dialogs
-    show_rename_dialog: bool,
-    rename_playlist_name: String,
-    show_delete_playlist_dialog: bool,
-    playlist_to_delete: Option<String>,
+            ui.label(lang.tr("theme_rounding"));
+            ui.add(egui::Slider::new(&mut self.theme.rounding, theme::ROUNDING_RANGE));
+            ui.end_row();
 
-    // Language
-    language: Language,
-}
+            ui.label(lang.tr("theme_spacing"));
+            ui.add(egui::Slider::new(&mut self.theme.spacing, theme::SPACING_RANGE));
+            ui.end_row();
 
-impl MusicPlayerApp {
-    fn new(audio_tx: Sender<AudioCommand>, audio_rx: Receiver<AudioStatus>, data: Arc<Mutex<PlaylistsManager>>, port: u16, port_tx: mpsc::UnboundedSender<u16>, cc: &eframe::CreationContext<'_>) -> Self {
-        // Load Config for Lyrics
-        let config = AppConfig::load();
-        
-        // Load Chinese font
-        let mut fonts = egui::FontDefinitions::default();
-        if let Ok(font_data) = std::fs::read("C:\\Windows\\Fonts\\msyh.ttc") {
-            fonts.font_data.insert("msyh".to_owned(), egui::FontData::from_owned(font_data).into());
-            fonts.families.get_mut(&egui::FontFamily::Proportional).unwrap().insert(0, "msyh".to_owned());
-            fonts.families.get_mut(&egui::FontFamily::Monospace).unwrap().push("msyh".to_owned());
-            cc.egui_ctx.set_fonts(fonts);
-        }
+            ui.label(lang.tr("name"));
+            ui.text_edit_singleline(&mut self.theme.name);
+            ui.end_row();
+        });
 
-        let lyrics_state = Arc::new(Mutex::new(LyricsState {
-            current_lyric: "".to_string(),
-            show_desktop_lyrics: false,
-            font_size: config.lyrics_font_size,
-            text_color: egui::Color32::from_rgba_unmultiplied(config.lyrics_text_color[0], config.lyrics_text_color[1], config.lyrics_text_color[2], config.lyrics_text_color[3]),
-            bg_color: egui::Color32::from_rgba_unmultiplied(config.lyrics_bg_color[0], config.lyrics_bg_color[1], config.lyrics_bg_color[2], config.lyrics_bg_color[3]),
-        }));
+        ui.horizontal(|ui| {
+            let name = self.theme.name.trim().to_string();
+            let is_built_in = Theme::built_in().iter().any(|t| t.name == name);
+            if ui.add_enabled(!name.is_empty() && !is_built_in, egui::Button::new(lang.tr("save_theme")))
+                .on_disabled_hover_text(lang.tr("theme_name_hint"))
+                .clicked()
+            {
+                self.theme.name = name.clone();
+                match self.themes.iter_mut().find(|t| t.name == name) {
+                    Some(preset) => *preset = self.theme.clone(),
+                    None => self.themes.push(self.theme.clone()),
+                }
+            }
+            let is_saved = self.themes.iter().any(|t| t.name == self.theme.name);
+            if ui.add_enabled(is_saved, egui::Button::new(lang.tr("delete_theme"))).clicked() {
+                self.themes.retain(|t| t.name != self.theme.name);
+            }
+            if ui.button(lang.tr("import_theme")).clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+                    match std::fs::read_to_string(&path).map_err(|e| e.to_string())
+                        .and_then(|text| serde_json::from_str::<Theme>(&text).map_err(|e| e.to_string()))
+                    {
+                        Ok(mut theme) => {
+                            // Built-in presets keep their name
+                            if theme.name.trim().is_empty() || Theme::built_in().iter().any(|t| t.name == theme.name) {
+                                theme.name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                            }
+                            self.themes.retain(|t| t.name != theme.name);
+                            self.themes.push(theme.clone());
+                            self.theme = theme;
+                        }
+                        Err(e) => self.error_message = Some(format!("{:?}: {}", path, e)),
+                    }
+                }
+            }
+            if ui.button(lang.tr("export_theme")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_file_name(format!("{}.json", self.theme.name))
+                    .save_file()
+                {
+                    if let Err(e) = storage::save_json(&path, &self.theme, true) {
+                        self.error_message = Some(format!("{:?}: {}", path, e));
+                    }
+                }
+            }
+        });
+    }
 
-        Self {
-            audio_tx,
-            audio_rx,
-            data,
-            volume: 1.0,
-            player_status: PlayerStatus::Ready,
-            api_port: port,
-            port_tx,
-            port_input: port.to_string(),
-            new_playlist_name: "".to_string(),
-            current_playing_file: None,
-            lyrics: Vec::new(),
-            lyrics_state,
-            show_lyrics_settings: false,
-            ui_bg_color: egui::Color32::from_rgba_unmultiplied(config.ui_bg_color[0], config.ui_bg_color[1], config.ui_bg_color[2], config.ui_bg_color[3]),
-            playback_mode: PlaybackMode::Order,
-            current_position: Duration::from_secs(0),
-            total_duration: Duration::from_secs(0),
-            is_playing: false,
-            is_seeking: false,
-            seek_target: None,
-            last_sync_time: None,
-            show_duplicate_dialog: false,
-            pending_files: Vec::new(),
-            show_rename_dialog: false,
-            rename_playlist_name: "".to_string(),
-            show_delete_playlist_dialog: false,
-            playlist_to_delete: None,
-            language: Language::Chinese,
+    /// Side panel listing every lyric line, kept centred on the current one.
+    /// Clicking a line seeks to it; scrolling by hand pauses the auto-follow.
+    fn show_lyrics_panel(&mut self, ctx: &egui::Context, position: Duration) {
+        let current = self.lyrics.line_at(position);
+        let (text_color, translation_color, translation_scale) = {
+            let state = self.lyrics_state.lock().unwrap();
+            (state.text_color, state.translation_color, state.translation_scale())
+        };
+        let mut seek_to = None;
+        egui::SidePanel::right("lyrics_panel")
+            .resizable(true)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                if self.lyrics.is_empty() {
+                    ui.centered_and_justified(|ui| {
+                        ui.weak(self.language.tr("no_lyrics"));
+                    });
+                    return;
+                }
+
+                let manual_scroll = ui.rect_contains_pointer(ui.max_rect()) && ui.input(|i| {
+                    i.raw_scroll_delta.y != 0.0 || (i.pointer.primary_down() && i.pointer.delta().y != 0.0)
+                });
+                if manual_scroll {
+                    self.lyrics_follow_paused_until = Some(Instant::now() + LYRICS_FOLLOW_PAUSE);
+                }
+                let following = self.lyrics_follow_paused_until.is_none_or(|until| Instant::now() >= until);
+
+                // Padding so the first and last lines can be centred too
+                let padding = ui.available_height() / 2.0;
+                egui::ScrollArea::vertical()
+                    .id_salt("lyrics_panel_scroll")
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        ui.add_space(padding);
+                        ui.vertical_centered(|ui| {
+                            for (i, line) in self.lyrics.lines.iter().enumerate() {
+                                let text = if line.text.is_empty() { "♪" } else { line.text.as_str() };
+                                let is_current = current == Some(i);
+                                let rich = if is_current {
+                                    egui::RichText::new(text).size(18.0).color(text_color).strong()
+                                } else {
+                                    egui::RichText::new(text).size(15.0).weak()
+                                }.family(fonts::lyrics_family());
+                                let mut response = ui.add(egui::Label::new(rich).sense(egui::Sense::click()))
+                                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                if let Some(translation) = &line.translation {
+                                    let rich = if is_current {
+                                        egui::RichText::new(translation).size(18.0 * translation_scale).color(translation_color)
+                                    } else {
+                                        egui::RichText::new(translation).size(15.0 * translation_scale).weak()
+                                    }.family(fonts::lyrics_family());
+                                    // Clicking either line seeks, and both are kept in view
+                                    response = response.union(ui.add(egui::Label::new(rich).sense(egui::Sense::click()))
+                                        .on_hover_cursor(egui::CursorIcon::PointingHand));
+                                }
+                                if response.clicked() {
+                                    seek_to = Some(line.time);
+                                }
+                                if is_current && following {
+                                    response.scroll_to_me(Some(egui::Align::Center));
+                                }
+                            }
+                        });
+                        ui.add_space(padding);
+                    });
+            });
+
+        if let Some(target) = seek_to {
+            self.seek(target);
+            self.lyrics_follow_paused_until = None;
         }
     }
 
-    fn play_file(&mut self, path: PathBuf) {
-        let _ = self.audio_tx.send(AudioCommand::PlayFile(path.clone()));
-        self.current_playing_file = Some(path.clone());
-        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-        self.player_status = PlayerStatus::Playing(file_name.to_string());
-        self.is_playing = true;
-        self.last_sync_time = Some(Instant::now());
-        self.current_position = Duration::from_secs(0);
+    /// Plays the next existing track from the queue, remembering where the
+    /// list was interrupted. Returns false if the queue had nothing to play.
+    fn play_from_queue(&mut self) -> bool {
+        let mut data = self.data.lock().unwrap();
+        while !data.queue.is_empty() {
+            let path = data.queue.remove(0);
+            if path.exists() {
+                data.save();
+                drop(data);
+                if self.queue_resume.is_none() {
+                    self.queue_resume = self.current_playing_file.clone();
+                }
+                self.play_file(path);
+                return true;
+            }
+        }
+        false
     }
 
     fn play_next(&mut self) {
+        // Queued tracks come first, except when repeating a single track
+        if (self.playback_mode != PlaybackMode::SingleLoop || self.current_playing_file.is_none()) && self.play_from_queue() {
+            return;
+        }
+
         let data = self.data.lock().unwrap();
         if let Some(list) = data.lists.get(&data.current_name) {
             if list.is_empty() { return; }
@@ -775,6 +3572,11 @@ impl MusicPlayerApp {
             } else {
                 None
             };
+            // After the queue, carry on from where the list was interrupted
+            let current_idx = current_idx.or_else(|| {
+                self.queue_resume.as_ref().and_then(|resume| list.iter().position(|p| &p.path == resume))
+            });
+            self.queue_resume = None;
 
             let next_idx = match self.playback_mode {
                 PlaybackMode::Single => None,
@@ -812,6 +3614,188 @@ impl MusicPlayerApp {
             }
         }
     }
+
+    /// Next / Previous from a shortcut or media key. Unlike `play_next` this
+    /// always moves to another track, whatever the playback mode; it only wraps
+    /// around the list in list loop mode. Previous restarts the track first if
+    /// it has been playing for a few seconds.
+    fn skip_track(&mut self, forward: bool) {
+        if self.current_playing_file.is_none() {
+            self.play_next();
+            return;
+        }
+        if forward && self.play_from_queue() {
+            return;
+        }
+        if !forward && self.display_position() > Duration::from_secs(3) {
+            self.seek(Duration::ZERO);
+            return;
+        }
+
+        let data = self.data.lock().unwrap();
+        let Some(list) = data.lists.get(&data.current_name).filter(|list| !list.is_empty()) else { return };
+        let current_idx = self.current_playing_file.as_ref()
+            .and_then(|curr| list.iter().position(|p| &p.path == curr))
+            .or_else(|| self.queue_resume.as_ref().and_then(|resume| list.iter().position(|p| &p.path == resume)));
+        let wrap = self.playback_mode == PlaybackMode::ListLoop;
+        let target = match current_idx {
+            None => Some(0),
+            Some(idx) if forward && idx + 1 < list.len() => Some(idx + 1),
+            Some(idx) if !forward && idx > 0 => Some(idx - 1),
+            Some(_) if wrap => Some(if forward { 0 } else { list.len() - 1 }),
+            Some(_) => None,
+        };
+        let path = target.and_then(|idx| list.get(idx)).map(|item| item.path.clone());
+        drop(data);
+
+        match path {
+            Some(path) => {
+                self.queue_resume = None;
+                self.play_file(path);
+            }
+            // At the start of the list without wrapping: restart the track
+            None if !forward => self.seek(Duration::ZERO),
+            None => {}
+        }
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::PlayPause if self.is_playing => self.pause(),
+            Action::PlayPause | Action::Play => {
+                if !self.is_playing {
+                    self.resume();
+                }
+            }
+            Action::Pause => {
+                if self.is_playing {
+                    self.pause();
+                }
+            }
+            Action::Stop => self.stop(),
+            Action::Next => self.skip_track(true),
+            Action::Previous => self.skip_track(false),
+            Action::SeekForward | Action::SeekBackward => {
+                if self.current_playing_file.is_some() {
+                    let position = self.display_position();
+                    let target = if action == Action::SeekForward {
+                        (position + SEEK_STEP).min(self.total_duration)
+                    } else {
+                        position.saturating_sub(SEEK_STEP)
+                    };
+                    self.seek(target);
+                }
+            }
+            Action::VolumeUp => self.set_volume(self.volume + VOLUME_STEP),
+            Action::VolumeDown => self.set_volume(self.volume - VOLUME_STEP),
+            Action::ToggleDesktopLyrics => {
+                let mut state = self.lyrics_state.lock().unwrap();
+                state.show_desktop_lyrics = !state.show_desktop_lyrics;
+            }
+        }
+    }
+
+    /// Runs the actions for shortcuts pressed this frame and for media keys.
+    /// Keys are left alone while a widget (e.g. a text field) has the keyboard
+    /// or a new shortcut is being recorded in the settings.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let mut requests = Vec::new();
+        if self.capturing_shortcut.is_none() && ctx.memory(|m| m.focused().is_none()) {
+            for (shortcut, action) in shortcuts::compile(&self.shortcuts) {
+                if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                    requests.push(MediaRequest::Action(action));
+                }
+            }
+        }
+        requests.extend(self.media_rx.try_iter());
+        if requests.is_empty() {
+            return;
+        }
+        for request in requests {
+            match request {
+                MediaRequest::Action(action) => self.run_action(action),
+                MediaRequest::SeekTo(target) => {
+                    if self.current_playing_file.is_some() {
+                        self.seek(target.min(self.total_duration));
+                    }
+                }
+                MediaRequest::SetVolume(volume) => self.set_volume(volume),
+            }
+        }
+        ctx.request_repaint();
+    }
+
+    /// Publishes the playback state to MPRIS clients.
+    #[cfg(target_os = "linux")]
+    fn publish_to_mpris(&self, position: Duration) {
+        let Some(mpris) = &self.mpris else { return };
+        let status = if self.is_playing {
+            mpris::PlaybackStatus::Playing
+        } else if matches!(self.player_status, PlayerStatus::Paused) {
+            mpris::PlaybackStatus::Paused
+        } else {
+            mpris::PlaybackStatus::Stopped
+        };
+        mpris.update(self.current_playing_file.as_deref(), status, position, self.total_duration, self.volume);
+    }
+
+    /// Lists every action with its shortcut; clicking a shortcut records a new
+    /// one from the next key press.
+    fn show_shortcut_settings(&mut self, ui: &mut egui::Ui) {
+        let lang = self.language;
+        if let Some(action) = self.capturing_shortcut {
+            // Taken out of the input so it doesn't also trigger anything
+            let pressed = ui.ctx().input_mut(|i| {
+                let index = i.events.iter().position(|e| matches!(e, egui::Event::Key { pressed: true, .. }))?;
+                match i.events.remove(index) {
+                    egui::Event::Key { key, modifiers, .. } => Some((key, modifiers)),
+                    _ => None,
+                }
+            });
+            match pressed {
+                Some((egui::Key::Escape, _)) => self.capturing_shortcut = None,
+                Some((key, modifiers)) => {
+                    let keys = shortcuts::format(&shortcuts::from_key_press(key, modifiers));
+                    // A key combination does one thing
+                    self.shortcuts.retain(|_, bound| *bound != keys);
+                    self.shortcuts.insert(action, keys);
+                    self.capturing_shortcut = None;
+                }
+                None => {}
+            }
+        }
+
+        egui::Grid::new("shortcuts_grid").num_columns(3).show(ui, |ui| {
+            for action in shortcuts::ACTIONS {
+                ui.label(action.as_str(lang));
+                let text = if self.capturing_shortcut == Some(action) {
+                    lang.tr("press_a_key").to_string()
+                } else {
+                    match self.shortcuts.get(&action) {
+                        Some(keys) if shortcuts::parse(keys).is_some() => keys.clone(),
+                        Some(keys) => lang.trf("invalid_shortcut", &[("keys", keys)]),
+                        None => lang.tr("unbound").to_string(),
+                    }
+                };
+                let button = ui.add(egui::Button::new(text).selected(self.capturing_shortcut == Some(action)));
+                if button.clicked() {
+                    button.surrender_focus();
+                    self.capturing_shortcut = Some(action);
+                }
+                if ui.add_enabled(self.shortcuts.contains_key(&action), egui::Button::new("✖")).on_hover_text(lang.tr("clear_shortcut")).clicked() {
+                    self.shortcuts.remove(&action);
+                }
+                ui.end_row();
+            }
+        });
+        if self.capturing_shortcut.is_some() {
+            ui.label(lang.tr("shortcut_capture_hint"));
+        }
+        if ui.button(lang.tr("reset_shortcuts")).clicked() {
+            self.shortcuts = shortcuts::default_bindings();
+            self.capturing_shortcut = None;
+        }
+    }
 }
 
 impl eframe::App for MusicPlayerApp {
@@ -822,7 +3806,7 @@ impl eframe::App for MusicPlayerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Apply UI Settings
         let mut style = (*ctx.style()).clone();
-        style.visuals.panel_fill = self.ui_bg_color;
+        self.theme.apply(&mut style);
         style.visuals.window_fill = egui::Color32::TRANSPARENT; // Ensure transparent background for viewports
         ctx.set_style(style);
 
@@ -845,11 +3829,38 @@ impl eframe::App for MusicPlayerApp {
                     }
                     self.total_duration = duration;
                     self.is_playing = is_playing;
+                    {
+                        let mut now_playing = self.now_playing.lock().unwrap();
+                        now_playing.position = position;
+                        now_playing.is_playing = is_playing;
+                        now_playing.synced_at = Some(Instant::now());
+                    }
+                    if let Some(session) = &mut self.listening {
+                        // Count position advances rather than wall time, so seeks don't count
+                        if is_playing && position > session.last_position {
+                            let delta = position - session.last_position;
+                            if delta < Duration::from_secs(2) {
+                                session.listened += delta;
+                            }
+                        }
+                        session.last_position = position;
+                        session.duration = duration;
+                    }
                 }
                 AudioStatus::Finished => {
+                    self.finish_listening(true);
                     self.play_next();
                 }
                 AudioStatus::Started(path) => {
+                    // A new track while one is still playing is a manual change
+                    self.finish_listening(false);
+                    self.listening = Some(ListenSession {
+                        path: path.clone(),
+                        started: unix_now(),
+                        listened: Duration::ZERO,
+                        last_position: Duration::ZERO,
+                        duration: Duration::ZERO,
+                    });
                     self.current_playing_file = Some(path.clone());
                     let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
                     self.player_status = PlayerStatus::Playing(file_name);
@@ -858,65 +3869,155 @@ impl eframe::App for MusicPlayerApp {
                     self.current_position = Duration::from_secs(0);
                     
                     // Load Lyrics
-                    self.lyrics.clear();
-                    self.lyrics_state.lock().unwrap().current_lyric.clear();
-                    let lrc_path = path.with_extension("lrc");
-                    if lrc_path.exists() {
-                        if let Ok(content) = std::fs::read_to_string(lrc_path) {
-                            self.lyrics = parse_lrc(&content);
-                        }
+                    {
+                        let mut state = self.lyrics_state.lock().unwrap();
+                        state.current_lyric.clear();
+                        state.highlight_chars = None;
+                        state.current_translation.clear();
+                        state.next_lyric.clear();
                     }
+                    // Finding them can mean reading tags and listing folders
+                    self.lyrics = Lyrics::default();
+                    let (tx, ctx) = (self.lyrics_tx.clone(), ctx.clone());
+                    let (track, sources, dirs) = (path.clone(), self.lyrics_sources.clone(), self.lyrics_dirs.clone());
+                    thread::spawn(move || {
+                        let lyrics = lyrics::load(&track, &sources, &dirs);
+                        let _ = tx.send((track, lyrics));
+                        ctx.request_repaint();
+                    });
+                    let mut now_playing = self.now_playing.lock().unwrap();
+                    now_playing.path = Some(path);
+                    now_playing.position = Duration::ZERO;
+                    now_playing.is_playing = true;
+                    now_playing.synced_at = Some(Instant::now());
+                    now_playing.lyrics = Lyrics::default();
+                    now_playing.lyrics_replaced = false;
                 }
             }
         }
         
-        // Calculate current display position
-        let mut display_pos = self.current_position;
-        if self.is_playing && !self.is_seeking && self.seek_target.is_none() {
-            if let Some(last_time) = self.last_sync_time {
-                let elapsed = last_time.elapsed();
-                display_pos += elapsed;
-                if display_pos > self.total_duration {
-                    display_pos = self.total_duration;
+        // Lyrics found for the playing track, or uploaded through the API
+        {
+            let mut now_playing = self.now_playing.lock().unwrap();
+            while let Ok((path, lyrics)) = self.lyrics_rx.try_recv() {
+                // Skip lookups for tracks since changed, or replaced by an upload
+                if now_playing.path.as_ref() == Some(&path) && now_playing.lyrics.is_empty() {
+                    now_playing.lyrics = lyrics.clone();
+                    self.lyrics = lyrics;
                 }
             }
+            if std::mem::take(&mut now_playing.lyrics_replaced) {
+                self.lyrics = now_playing.lyrics.clone();
+            }
+        }
+
+        // Files and folders dropped from the OS
+        let dropped: Vec<PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+        if !dropped.is_empty() {
+            let tx = self.dropped_tx.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let _ = tx.send(collect_audio_files(dropped));
+                ctx.request_repaint();
+            });
+        }
+        while let Ok(files) = self.dropped_rx.try_recv() {
+            self.add_files_to_current(files);
+        }
+        while let Ok(errors) = self.tag_errors_rx.try_recv() {
+            if !errors.is_empty() {
+                self.error_message = Some(errors.join("\n"));
+            }
         }
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let current_name = self.data.lock().unwrap().current_name.clone();
+            let text = self.language.trf("drop_to_add", &[("playlist", &current_name)]);
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_overlay")));
+            let screen_rect = ctx.content_rect();
+            painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(180));
+            painter.text(screen_rect.center(), egui::Align2::CENTER_CENTER, text, egui::FontId::proportional(20.0), egui::Color32::WHITE);
+        }
+
+        let display_pos = self.display_position();
+        #[cfg(target_os = "linux")]
+        self.publish_to_mpris(display_pos);
 
         // Update Lyrics
         if !self.lyrics.is_empty() {
-            let mut new_lyric = "";
-            for (time, text) in &self.lyrics {
-                if *time <= display_pos {
-                    new_lyric = text;
-                } else {
-                    break;
-                }
-            }
+            let current = self.lyrics.line_at(display_pos);
+            let new_lyric = current.map_or("", |i| self.lyrics.lines[i].text.as_str());
+            let new_translation = current.and_then(|i| self.lyrics.lines[i].translation.as_deref()).unwrap_or("");
+            let next_index = current.map_or(0, |i| i + 1);
+            let new_next = self.lyrics.lines[next_index.min(self.lyrics.lines.len())..].iter()
+                .find(|line| !line.text.is_empty())
+                .map_or("", |line| line.text.as_str());
+            let highlight_chars = current.and_then(|i| {
+                let line_end = self.lyrics.lines.get(i + 1).map(|next| next.time);
+                self.lyrics.lines[i].highlight_chars(display_pos, line_end)
+            });
             
             let mut state = self.lyrics_state.lock().unwrap();
             if state.current_lyric != new_lyric {
                 state.current_lyric = new_lyric.to_string();
             }
+            if state.current_translation != new_translation {
+                state.current_translation = new_translation.to_string();
+            }
+            if state.next_lyric != new_next {
+                state.next_lyric = new_next.to_string();
+            }
+            state.highlight_chars = highlight_chars;
         }
 
         // Desktop Lyrics Window
-        let show_desktop_lyrics = self.lyrics_state.lock().unwrap().show_desktop_lyrics;
+        let (show_desktop_lyrics, locked) = {
+            let mut state = self.lyrics_state.lock().unwrap();
+            if state.show_desktop_lyrics && !self.desktop_lyrics_was_shown {
+                self.desktop_lyrics_geometry = (state.window_pos, state.window_size);
+            }
+            // Save the geometry once the window has been still for a moment
+            if state.window_changed_at.is_some_and(|t| t.elapsed() > Duration::from_secs(1)) {
+                state.window_changed_at = None;
+                let mut config = AppConfig::load();
+                config.desktop_lyrics_pos = state.window_pos.map(|pos| [pos.x, pos.y]);
+                config.desktop_lyrics_size = [state.window_size.x, state.window_size.y];
+                config.save();
+            }
+            (state.show_desktop_lyrics, state.locked)
+        };
+        self.desktop_lyrics_was_shown = show_desktop_lyrics;
         if show_desktop_lyrics {
             let lyrics_viewport_id = egui::ViewportId::from_hash_of("lyrics_viewport");
             let lyrics_state = self.lyrics_state.clone();
+            let (position, size) = self.desktop_lyrics_geometry;
+            let mut builder = egui::ViewportBuilder::default()
+                .with_title("Lyrics")
+                .with_inner_size(size.max(egui::vec2(200.0, 40.0)))
+                .with_transparent(true)
+                .with_decorations(false)
+                .with_always_on_top()
+                .with_taskbar(false)
+                .with_resizable(!locked)
+                .with_mouse_passthrough(locked);
+            if let Some(position) = position {
+                builder = builder.with_position(position);
+            }
             ctx.show_viewport_deferred(
                 lyrics_viewport_id,
-                egui::ViewportBuilder::default()
-                    .with_title("Lyrics")
-                    .with_inner_size([800.0, 100.0])
-                    .with_transparent(true)
-                    .with_decorations(false)
-                    .with_always_on_top()
-                    .with_taskbar(false)
-                    .with_resizable(true),
+                builder,
                 move |ctx, _class| {
                     let mut state = lyrics_state.lock().unwrap();
 
+                    // Track moves and resizes so they can be saved
+                    let (outer, inner) = ctx.input(|i| (i.viewport().outer_rect, i.viewport().inner_rect));
+                    if let (Some(outer), Some(inner)) = (outer, inner) {
+                        if state.window_pos != Some(outer.min) || state.window_size != inner.size() {
+                            state.window_pos = Some(outer.min);
+                            state.window_size = inner.size();
+                            state.window_changed_at = Some(Instant::now());
+                        }
+                    }
+
                     // Use Frame::NONE to ensure no default background/shadow/stroke is drawn
                     let frame = egui::Frame::NONE;
 
@@ -928,13 +4029,31 @@ impl eframe::App for MusicPlayerApp {
                             ui.painter().rect_filled(rect, 0.0, state.bg_color);
                         }
                         
-                        // Draw text
-                        ui.centered_and_justified(|ui| {
-                             ui.label(egui::RichText::new(&state.current_lyric)
-                                .size(state.font_size)
-                                .color(state.text_color)
-                                .strong());
-                        });
+                        // Draw text: the current line, its translation and
+                        // optionally the next line, sharing the height by font size
+                        let effects = state.effects();
+                        let highlight = state.highlight_chars.map(|chars| (chars, state.highlight_color));
+                        let next_color = state.text_color.gamma_multiply(0.6);
+                        let mut lines = vec![(state.current_lyric.as_str(), state.font_size, state.text_color, highlight)];
+                        if !state.current_translation.is_empty() {
+                            lines.push((state.current_translation.as_str(), state.translation_font_size, state.translation_color, None));
+                        }
+                        if state.two_lines && !state.next_lyric.is_empty() {
+                            lines.push((state.next_lyric.as_str(), state.font_size * 0.75, next_color, None));
+                        }
+                        let total: f32 = lines.iter().map(|line| line.1).sum();
+                        let mut top = rect.top();
+                        for (text, font_size, color, highlight) in lines {
+                            let height = rect.height() * font_size / total;
+                            let line_rect = egui::Rect::from_x_y_ranges(rect.x_range(), top..=top + height);
+                            paint_lyric_line(ui, line_rect, text, font_size, color, highlight, effects);
+                            top += height;
+                        }
+
+                        // Locked: click-through, so no dragging or close button
+                        if state.locked {
+                            return;
+                        }
 
                         // Close button (top-right)
                         let close_btn_size = 24.0;
@@ -992,145 +4111,113 @@ impl eframe::App for MusicPlayerApp {
         // Status Bar (Bottom)
         egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                let status_label = match self.language {
-                    Language::Chinese => "状态",
-                    Language::English => "Status",
-                };
+                let status_label = self.language.tr("status");
                 let status_text = match &self.player_status {
-                    PlayerStatus::Ready => match self.language {
-                        Language::Chinese => "就绪".to_string(),
-                        Language::English => "Ready".to_string(),
-                    },
-                    PlayerStatus::Playing(name) => match self.language {
-                        Language::Chinese => format!("正在播放: {}", name),
-                        Language::English => format!("Playing: {}", name),
-                    },
-                    PlayerStatus::Finished => match self.language {
-                        Language::Chinese => "播放结束".to_string(),
-                        Language::English => "Playback Finished".to_string(),
-                    },
-                    PlayerStatus::Stopped => match self.language {
-                        Language::Chinese => "已停止".to_string(),
-                        Language::English => "Stopped".to_string(),
-                    },
-                    PlayerStatus::Paused => match self.language {
-                        Language::Chinese => "已暂停".to_string(),
-                        Language::English => "Paused".to_string(),
-                    },
+                    PlayerStatus::Ready => self.language.tr("ready").to_string(),
+                    PlayerStatus::Playing(name) => self.language.trf("playing", &[("name", name)]),
+                    PlayerStatus::Finished => self.language.tr("playback_finished").to_string(),
+                    PlayerStatus::Stopped => self.language.tr("stopped").to_string(),
+                    PlayerStatus::Paused => self.language.tr("paused").to_string(),
                 };
                 let full_text = format!("{}: {}", status_label, status_text);
                 let available_width = ui.available_width();
                 scroll_label(ui, &full_text, available_width.max(100.0), true, ui.input(|i| i.time));
             });
             ui.horizontal(|ui| {
-                let port_label = match self.language {
-                    Language::Chinese => "API 端口:",
-                    Language::English => "API Port:",
-                };
+                let port_label = self.language.tr("api_port");
                 ui.label(port_label);
                 ui.add(egui::TextEdit::singleline(&mut self.port_input).desired_width(50.0));
-                let apply_label = match self.language {
-                    Language::Chinese => "应用",
-                    Language::English => "Apply",
-                };
+                let apply_label = self.language.tr("apply");
                 if ui.button(apply_label).clicked() {
                     if let Ok(new_port) = self.port_input.parse::<u16>() {
                         if new_port != self.api_port {
                             self.api_port = new_port;
                             let state = self.lyrics_state.lock().unwrap();
-                            let config = AppConfig { 
-                                port: new_port,
-                                lyrics_font_size: state.font_size,
-                                lyrics_text_color: state.text_color.to_array(),
-                                lyrics_bg_color: state.bg_color.to_array(),
-                                ui_bg_color: self.ui_bg_color.to_array(),
-                            };
+                            let mut config = AppConfig::load();
+                            config.port = new_port;
+                            state.store(&mut config);
+                            config.theme = self.theme.clone();
                             config.save();
                             let _ = self.port_tx.send(new_port);
                         }
                     }
                 }
-                let current_label = match self.language {
-                    Language::Chinese => "当前",
-                    Language::English => "Current",
-                };
+                let current_label = self.language.tr("current");
                 ui.label(format!("({}: {})", current_label, self.api_port));
             });
         });
 
+        if self.show_lyrics_panel {
+            self.show_lyrics_panel(ctx, display_pos);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.heading("Rweb Music Player");
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let previous_language = self.language;
                     egui::ComboBox::from_id_salt("lang_selector")
-                        .selected_text(self.language.as_str())
+                        .selected_text(self.language.name())
                         .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut self.language, Language::Chinese, "中文");
-                            ui.selectable_value(&mut self.language, Language::English, "English");
+                            for language in Language::all() {
+                                ui.selectable_value(&mut self.language, language, language.name());
+                            }
                         });
+                    if self.language != previous_language {
+                        let mut config = AppConfig::load();
+                        config.language = self.language.code().to_string();
+                        config.save();
+                    }
                     
-                    let lyrics_label = match self.language {
-                        Language::Chinese => "桌面歌词",
-                        Language::English => "Desktop Lyrics",
-                    };
+                    let lyrics_label = self.language.tr("desktop_lyrics");
                     let mut state = self.lyrics_state.lock().unwrap();
                     if ui.selectable_label(state.show_desktop_lyrics, lyrics_label).clicked() {
                         state.show_desktop_lyrics = !state.show_desktop_lyrics;
                     }
+                    // A locked window ignores the mouse, so it's unlocked from here
+                    if state.show_desktop_lyrics {
+                        let lock_label = if state.locked { "🔒" } else { "🔓" };
+                        if ui.selectable_label(state.locked, lock_label).on_hover_text(self.language.tr("lock_desktop_lyrics")).clicked() {
+                            state.locked = !state.locked;
+                            let mut config = AppConfig::load();
+                            config.desktop_lyrics_locked = state.locked;
+                            config.save();
+                        }
+                    }
+                    drop(state);
+
+                    let panel_label = self.language.tr("lyrics_panel");
+                    if ui.selectable_label(self.show_lyrics_panel, panel_label).clicked() {
+                        self.show_lyrics_panel = !self.show_lyrics_panel;
+                    }
                     
-                    if ui.button("⚙").on_hover_text(match self.language {
-                        Language::Chinese => "歌词设置",
-                        Language::English => "Lyrics Settings",
-                    }).clicked() {
+                    if ui.button("⚙").on_hover_text(self.language.tr("lyrics_settings")).clicked() {
                         self.show_lyrics_settings = !self.show_lyrics_settings;
                     }
+
+                    let playing = self.current_playing_file.clone();
+                    if ui.add_enabled(playing.is_some(), egui::Button::new("✏")).on_hover_text(self.language.tr("edit_lyrics_timing")).clicked() {
+                        if let Some(path) = playing {
+                            let content = lyrics::resolve(&path, &self.lyrics_sources, &self.lyrics_dirs).unwrap_or_default();
+                            self.lyrics_editor = Some(LyricsEditor::new(path, &content));
+                        }
+                    }
                 });
             });
 
             // Playback Controls
             ui.horizontal(|ui| {
-                let play_label = if self.is_playing { 
-                    match self.language {
-                        Language::Chinese => "⏸ 暂停",
-                        Language::English => "⏸ Pause",
-                    }
-                } else { 
-                    match self.language {
-                        Language::Chinese => "▶ 播放",
-                        Language::English => "▶ Play",
-                    }
-                };
-                if ui.button(play_label).clicked() {
-                    if self.is_playing {
-                        let _ = self.audio_tx.send(AudioCommand::Pause);
-                        self.is_playing = false; // Immediate feedback
-                        self.player_status = PlayerStatus::Paused;
-                        self.last_sync_time = None;
-                    } else {
-                        if self.current_playing_file.is_some() {
-                            let _ = self.audio_tx.send(AudioCommand::Resume);
-                            self.is_playing = true;
-                            if let Some(path) = &self.current_playing_file {
-                                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                                self.player_status = PlayerStatus::Playing(name);
-                            }
-                            self.last_sync_time = Some(Instant::now());
-                        } else {
-                            // Try play first in list
-                            self.play_next();
-                        }
-                    }
-                }
-                let stop_label = match self.language {
-                    Language::Chinese => "⏹ 停止",
-                    Language::English => "⏹ Stop",
+                let play_label = if self.is_playing { 
+                    self.language.tr("pause")
+                } else { 
+                    self.language.tr("play")
                 };
+                if ui.button(play_label).clicked() {
+                    self.run_action(Action::PlayPause);
+                }
+                let stop_label = self.language.tr("stop");
                 if ui.button(stop_label).clicked() {
-                    let _ = self.audio_tx.send(AudioCommand::Stop);
-                    self.current_position = Duration::from_secs(0);
-                    self.is_playing = false;
-                    self.player_status = PlayerStatus::Stopped;
-                    self.last_sync_time = None;
+                    self.stop();
                 }
                 
                 // Mode Selector
@@ -1144,14 +4231,21 @@ impl eframe::App for MusicPlayerApp {
                     });
             });
 
-            // Main Window Lyrics Display
+            // Main Window Lyrics Display (the panel shows it otherwise)
             let state = self.lyrics_state.lock().unwrap();
-            if !state.current_lyric.is_empty() {
+            if !state.current_lyric.is_empty() && !self.show_lyrics_panel {
                 ui.vertical_centered(|ui| {
                     ui.label(egui::RichText::new(&state.current_lyric)
                         .size(18.0)
+                        .family(fonts::lyrics_family())
                         .color(state.text_color)
                         .strong());
+                    if !state.current_translation.is_empty() {
+                        ui.label(egui::RichText::new(&state.current_translation)
+                            .size(18.0 * state.translation_scale())
+                            .family(fonts::lyrics_family())
+                            .color(state.translation_color));
+                    }
                 });
             }
             drop(state);
@@ -1200,11 +4294,8 @@ impl eframe::App for MusicPlayerApp {
                     self.last_sync_time = None; 
                 }
                 if response.drag_stopped() {
-                    let target = Duration::from_secs_f32(value);
-                    let _ = self.audio_tx.send(AudioCommand::Seek(target));
-                    self.seek_target = Some(target);
+                    self.seek(Duration::from_secs_f32(value));
                     self.is_seeking = false;
-                    // Don't enable interpolation yet, wait for sync
                 }
                 
                 ui.label(format_time(self.total_duration));
@@ -1212,13 +4303,11 @@ impl eframe::App for MusicPlayerApp {
 
             // Volume Control
             ui.horizontal(|ui| {
-                let vol_label = match self.language {
-                    Language::Chinese => "音量",
-                    Language::English => "Volume",
-                };
+                let vol_label = self.language.tr("volume");
                 ui.label(vol_label);
-                if ui.add(egui::Slider::new(&mut self.volume, 0.0..=1.0)).changed() {
-                    let _ = self.audio_tx.send(AudioCommand::SetVolume(self.volume));
+                let mut volume = self.volume;
+                if ui.add(egui::Slider::new(&mut volume, 0.0..=1.0)).changed() {
+                    self.set_volume(volume);
                 }
             });
 
@@ -1228,46 +4317,64 @@ impl eframe::App for MusicPlayerApp {
             let mut data = self.data.lock().unwrap();
             
             ui.horizontal(|ui| {
-                let playlist_label = match self.language {
-                    Language::Chinese => "当前歌单:",
-                    Language::English => "Playlist:",
-                };
+                let playlist_label = self.language.tr("playlist");
                 ui.label(playlist_label);
+                // Smart playlists are marked with ⚡, the favourites list with ♥
+                let playlist_label = |data: &PlaylistsManager, name: &str| {
+                    if data.is_builtin(name) {
+                        format!("♥ {}", name)
+                    } else if data.is_smart(name) {
+                        format!("⚡ {}", name)
+                    } else {
+                        name.to_string()
+                    }
+                };
                 egui::ComboBox::from_id_salt("playlist_selector")
-                    .selected_text(&data.current_name)
+                    .selected_text(playlist_label(&data, &data.current_name))
                     .show_ui(ui, |ui| {
                         for name in data.lists.keys().cloned().collect::<Vec<_>>() {
-                            if ui.selectable_value(&mut data.current_name, name.clone(), &name).clicked() {
+                            let label = playlist_label(&data, &name);
+                            if ui.selectable_value(&mut data.current_name, name.clone(), label).clicked() {
                                 data.save();
                             }
                         }
                     });
                 
-                if ui.button("✏").on_hover_text(match self.language {
-                    Language::Chinese => "重命名当前歌单",
-                    Language::English => "Rename current playlist",
-                }).clicked() {
+                let builtin = data.is_builtin(&data.current_name);
+                if ui.add_enabled(!builtin, egui::Button::new("✏")).on_hover_text(self.language.tr("rename_current_playlist")).clicked() {
                     self.rename_playlist_name = data.current_name.clone();
                     self.show_rename_dialog = true;
                 }
 
-                if ui.button("🗑").on_hover_text(match self.language {
-                    Language::Chinese => "删除当前歌单",
-                    Language::English => "Delete current playlist",
-                }).clicked() {
+                if ui.add_enabled(!builtin, egui::Button::new("🗑")).on_hover_text(self.language.tr("delete_current_playlist")).clicked() {
                     if data.lists.len() > 1 {
                         self.playlist_to_delete = Some(data.current_name.clone());
                         self.show_delete_playlist_dialog = true;
                     }
                 }
+
+                let current_is_smart = data.is_smart(&data.current_name) && !builtin;
+                if ui.button("⚡").on_hover_text(self.language.tr(if current_is_smart { "edit_smart_playlist_rules" } else { "new_smart_playlist" })).clicked() {
+                    self.smart_editor = Some(match data.smart.get(&data.current_name).filter(|_| current_is_smart) {
+                        Some(smart) => SmartEditor {
+                            original_name: Some(data.current_name.clone()),
+                            name: data.current_name.clone(),
+                            playlist: smart.clone(),
+                            limit: smart.limit.map(|l| l.to_string()).unwrap_or_default(),
+                        },
+                        None => SmartEditor {
+                            original_name: None,
+                            name: String::new(),
+                            playlist: SmartPlaylist::default(),
+                            limit: String::new(),
+                        },
+                    });
+                }
             });
 
             ui.horizontal(|ui| {
                 ui.text_edit_singleline(&mut self.new_playlist_name);
-                let new_playlist_label = match self.language {
-                    Language::Chinese => "新建歌单",
-                    Language::English => "New Playlist",
-                };
+                let new_playlist_label = self.language.tr("new_playlist");
                 if ui.button(new_playlist_label).clicked() {
                     if !self.new_playlist_name.is_empty() {
                         data.lists.entry(self.new_playlist_name.clone()).or_default();
@@ -1285,47 +4392,55 @@ impl eframe::App for MusicPlayerApp {
 
             // File Management
             ui.horizontal(|ui| {
-                let add_file_label = match self.language {
-                    Language::Chinese => "添加文件",
-                    Language::English => "Add Files",
+                let read_only = {
+                    let data = self.data.lock().unwrap();
+                    data.is_smart(&data.current_name)
                 };
-                if ui.button(add_file_label).clicked() {
+                let add_file_label = self.language.tr("add_files");
+                if ui.add_enabled(!read_only, egui::Button::new(add_file_label)).clicked() {
                     if let Some(paths) = rfd::FileDialog::new().pick_files() {
-                        let mut data = self.data.lock().unwrap();
-                        let current_name = data.current_name.clone();
-                        let list = data.lists.entry(current_name.clone()).or_default();
-                        
-                        let mut duplicates = Vec::new();
-                        let mut non_duplicates = Vec::new();
-                        
-                        for path in paths {
-                            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                            if list.iter().any(|item| item.name == name) {
-                                duplicates.push(path);
-                            } else {
-                                non_duplicates.push(path);
-                            }
-                        }
-                        
-                        // Add non-duplicates immediately
-                        for path in non_duplicates {
-                            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                            list.insert(0, PlaylistItem { path, name });
+                        self.add_files_to_current(paths);
+                    }
+                }
+                let import_label = self.language.tr("import");
+                if ui.button(import_label).clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("Playlist", PLAYLIST_EXTENSIONS).pick_file() {
+                        // The file is read before taking the lock
+                        let imported = playlist_formats::import_playlist(&path).and_then(|paths| {
+                            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                            let mut data = self.data.lock().unwrap();
+                            let (name, _) = data.import_playlist(paths, None, &stem)?;
+                            data.current_name = name;
+                            data.save();
+                            Ok(())
+                        });
+                        match imported {
+                            Ok(()) => spawn_library_indexer(self.data.clone()),
+                            Err(e) => self.error_message = Some(format!("{:?}: {}", path, e)),
                         }
-                        
-                        if !duplicates.is_empty() {
-                            self.pending_files = duplicates;
-                            self.show_duplicate_dialog = true;
+                    }
+                }
+                let export_label = self.language.tr("export");
+                if ui.button(export_label).clicked() {
+                    let current_name = self.data.lock().unwrap().current_name.clone();
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("M3U8", &["m3u8"])
+                        .add_filter("M3U", &["m3u"])
+                        .add_filter("PLS", &["pls"])
+                        .add_filter("XSPF", &["xspf"])
+                        .set_file_name(format!("{}.m3u8", current_name))
+                        .save_file()
+                    {
+                        // Fall back to M3U8 if the dialog dropped the extension
+                        let format = PlaylistFormat::from_path(&path).unwrap_or(PlaylistFormat::M3u8);
+                        let data = self.data.lock().unwrap();
+                        if let Err(e) = data.export_playlist_file(&current_name, &path, Some(format), false) {
+                            self.error_message = Some(format!("{:?}: {}", path, e));
                         }
-                        
-                        data.save();
                     }
                 }
-                let clear_list_label = match self.language {
-                    Language::Chinese => "清空当前列表",
-                    Language::English => "Clear List",
-                };
-                if ui.button(clear_list_label).clicked() {
+                let clear_list_label = self.language.tr("clear_list");
+                if ui.add_enabled(!read_only, egui::Button::new(clear_list_label)).clicked() {
                     let mut data = self.data.lock().unwrap();
                     let current_name = data.current_name.clone();
                     if let Some(list) = data.lists.get_mut(&current_name) {
@@ -1338,42 +4453,261 @@ impl eframe::App for MusicPlayerApp {
             // Re-acquire lock for display
             let mut data = self.data.lock().unwrap();
 
-            let list_content_label = match self.language {
-                Language::Chinese => format!("列表内容 ({}) :", data.current_name),
-                Language::English => format!("Playlist Content ({}) :", data.current_name),
-            };
+            let list_content_label = self.language.trf("playlist_content", &[("playlist", &data.current_name)]);
             ui.label(list_content_label);
+
+            if !data.queue.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label(self.language.trf("queue_tracks", &[("n", &data.queue.len())]));
+                    if ui.small_button(self.language.tr("clear_queue")).clicked() {
+                        data.queue.clear();
+                        data.save();
+                    }
+                });
+            }
+
+            // Search Box
+            let search_id = egui::Id::new("playlist_search");
+            let mut search_enter = false;
+            let mut cursor_moved = false;
+            ui.horizontal(|ui| {
+                ui.label("🔍");
+                let filters = [
+                    RatingFilter::All,
+                    RatingFilter::Favourites,
+                    RatingFilter::AtLeast(1),
+                    RatingFilter::AtLeast(2),
+                    RatingFilter::AtLeast(3),
+                    RatingFilter::AtLeast(4),
+                    RatingFilter::AtLeast(5),
+                ];
+                egui::ComboBox::from_id_salt("rating_filter")
+                    .width(70.0)
+                    .selected_text(self.rating_filter.label(self.language))
+                    .show_ui(ui, |ui| {
+                        for filter in filters {
+                            if ui.selectable_value(&mut self.rating_filter, filter, filter.label(self.language)).changed() {
+                                self.search_cursor = 0;
+                            }
+                        }
+                    });
+                if ctx.memory(|m| m.has_focus(search_id)) {
+                    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+                        self.search_cursor += 1;
+                        cursor_moved = true;
+                    }
+                    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+                        self.search_cursor = self.search_cursor.saturating_sub(1);
+                        cursor_moved = true;
+                    }
+                }
+                let hint = self.language.tr("search_hint");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.search_query)
+                    .id(search_id)
+                    .hint_text(hint)
+                    .desired_width(f32::INFINITY));
+                if response.changed() {
+                    self.search_cursor = 0;
+                }
+                if response.lost_focus() {
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        search_enter = true;
+                        response.request_focus();
+                    } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        self.search_query.clear();
+                        self.search_cursor = 0;
+                    }
+                }
+                if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+                    response.request_focus();
+                }
+            });
             
+            // Column Headers (click to sort)
+            let current_name = data.current_name.clone();
+            // Smart playlists are ordered by their rules and can't be edited
+            let read_only = data.is_smart(&current_name);
+            let last_sort = self.last_sort.as_ref().filter(|(name, _)| *name == current_name).map(|(_, sort)| *sort);
+            let mut keep_sorted = data.sort_orders.contains_key(&current_name);
+            let keep_sorted_label = self.language.tr("keep_sorted");
+            if read_only {
+                ui.label(self.language.tr(if data.is_builtin(&current_name) { "favourites_playlist_hint" } else { "smart_playlist_read_only" }));
+            } else if ui.add_enabled(keep_sorted || last_sort.is_some(), egui::Checkbox::new(&mut keep_sorted, keep_sorted_label))
+                .on_disabled_hover_text(self.language.tr("keep_sorted_hint"))
+                .changed()
+            {
+                match last_sort.filter(|_| keep_sorted) {
+                    Some(sort) => {
+                        data.sort_orders.insert(current_name.clone(), sort);
+                    }
+                    None => {
+                        data.sort_orders.remove(&current_name);
+                    }
+                }
+                data.save();
+            }
+            let active_sort = data.sort_orders.get(&current_name).copied().or(last_sort);
+            let (header_rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 18.0), egui::Sense::hover());
+            let header_titles = ["#", "column.title", "column.artist", "column.album", "column.time", "column.rating", "column.plays", "column.last_played", "column.path"]
+                .map(|key| if key == "#" { key } else { self.language.tr(key) });
+            for ((cell, column), title) in playlist_column_rects(header_rect).into_iter().zip(PLAYLIST_COLUMNS).zip(header_titles) {
+                let arrow = match active_sort {
+                    Some(sort) if Some(sort.column) == column => if sort.descending { " ▼" } else { " ▲" },
+                    _ => "",
+                };
+                let sense = if read_only { egui::Sense::hover() } else { egui::Sense::click() };
+                let header_response = ui.interact(cell, ui.id().with(("playlist_header", title)), sense);
+                let color = if header_response.hovered() && column.is_some() && !read_only {
+                    ui.visuals().strong_text_color()
+                } else {
+                    ui.visuals().weak_text_color()
+                };
+                paint_cell_text(ui, cell, &format!("{}{}", title, arrow), color);
+                if let Some(column) = column {
+                    if header_response.clicked() {
+                        let descending = active_sort.is_some_and(|sort| sort.column == column && !sort.descending);
+                        let sort = PlaylistSort { column, descending };
+                        data.sort_list(&current_name, sort);
+                        if data.sort_orders.contains_key(&current_name) {
+                            data.sort_orders.insert(current_name.clone(), sort);
+                        }
+                        self.last_sort = Some((current_name.clone(), sort));
+                        data.save();
+                    }
+                }
+            }
+
             // Display Playlist
             let current_list = data.lists.get(&data.current_name).cloned().unwrap_or_default();
             let mut file_to_play = None;
-            let mut item_to_delete = None;
+            let mut bulk_action = None;
+            let mut item_to_move = None;
+            if self.selection_list != current_name {
+                self.selected.clear();
+                self.select_anchor = None;
+                self.selection_list = current_name.clone();
+            }
+            if !self.selected.is_empty() {
+                let present: HashSet<&PlaylistItem> = current_list.iter().collect();
+                self.selected.retain(|item| present.contains(item));
+            }
+            let mut other_playlists: Vec<String> = data.lists.keys()
+                .filter(|name| **name != current_name && !data.is_smart(name))
+                .cloned()
+                .collect();
+            other_playlists.sort();
+
+            // Indices into current_list, best match first while searching
+            let query = self.search_query.trim();
+            let mut visible: Vec<usize> = if query.is_empty() {
+                (0..current_list.len()).collect()
+            } else {
+                let mut scored: Vec<(usize, i64)> = current_list.iter().enumerate()
+                    .filter_map(|(index, item)| {
+                        fuzzy_score(query, &search_haystack(item, data.library.get(&item.path))).map(|score| (index, score))
+                    })
+                    .collect();
+                scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+                scored.into_iter().map(|(index, _)| index).collect()
+            };
+            if self.rating_filter != RatingFilter::All {
+                visible.retain(|&index| self.rating_filter.matches(data.library.get(&current_list[index].path)));
+            }
+            let filtering = !query.is_empty() || self.rating_filter != RatingFilter::All;
+            self.search_cursor = self.search_cursor.min(visible.len().saturating_sub(1));
+            let show_cursor = !query.is_empty() || ctx.memory(|m| m.has_focus(search_id));
+            if search_enter {
+                if let Some(item) = visible.get(self.search_cursor).map(|&index| &current_list[index]) {
+                    if item.path.exists() {
+                        file_to_play = Some(item.path.clone());
+                    }
+                }
+            }
+
+            if visible.is_empty() && filtering {
+                ui.label(self.language.tr("no_matches"));
+            }
 
             egui::ScrollArea::vertical().show(ui, |ui| {
-                for (index, item) in current_list.iter().enumerate() {
+                for (row, &index) in visible.iter().enumerate() {
+                    let item = &current_list[index];
                     let is_current = Some(&item.path) == self.current_playing_file.as_ref();
+                    let is_cursor = show_cursor && row == self.search_cursor;
                     let exists = item.path.exists();
                     
                     ui.horizontal(|ui| {
-                        let text = format!("{}. {}", index, item.name);
+                        let meta = data.library.get(&item.path).cloned().unwrap_or_default();
+                        let title = if meta.title.is_empty() { item.name.clone() } else { meta.title.clone() };
                         
-                        // Custom selectable label with scrolling
+                        // Custom selectable row, draggable to reorder
                         let available_width = ui.available_width();
-                        let (rect, response) = ui.allocate_exact_size(egui::vec2(available_width, 20.0), egui::Sense::click());
+                        let (rect, response) = ui.allocate_exact_size(egui::vec2(available_width, 20.0), egui::Sense::click_and_drag());
                         let is_hovered = response.hovered();
+                        let cells = playlist_column_rects(rect);
+                        let title_rect = cells[1];
 
                         if response.clicked() {
-                            if exists {
-                                file_to_play = Some(item.path.clone());
+                            let modifiers = ui.input(|i| i.modifiers);
+                            if modifiers.command {
+                                if !self.selected.remove(item) {
+                                    self.selected.insert(item.clone());
+                                }
+                                self.select_anchor = Some(item.clone());
+                            } else if modifiers.shift {
+                                // Range in display order, so it also works on filtered results
+                                let anchor_row = self.select_anchor.as_ref()
+                                    .and_then(|anchor| visible.iter().position(|&i| current_list[i] == *anchor));
+                                let (start, end) = match anchor_row {
+                                    Some(anchor_row) => (anchor_row.min(row), anchor_row.max(row)),
+                                    None => (row, row),
+                                };
+                                self.selected = visible[start..=end].iter().map(|&i| current_list[i].clone()).collect();
+                            } else {
+                                self.selected = HashSet::from([item.clone()]);
+                                self.select_anchor = Some(item.clone());
+                                if exists {
+                                    file_to_play = Some(item.path.clone());
+                                }
+                            }
+                        }
+                        if response.secondary_clicked() && !self.selected.contains(item) {
+                            self.selected = HashSet::from([item.clone()]);
+                            self.select_anchor = Some(item.clone());
+                        }
+
+                        if !read_only {
+                            response.dnd_set_drag_payload(index);
+                        }
+                        if response.dragged() && !read_only {
+                            ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+                        }
+                        // Insert before the hovered row when over its upper half, after it otherwise
+                        let pointer_y = ui.input(|i| i.pointer.interact_pos()).map_or(rect.center().y, |p| p.y);
+                        let insert_at = if pointer_y < rect.center().y { index } else { index + 1 };
+                        if response.dnd_hover_payload::<usize>().is_some() {
+                            let line_y = if insert_at == index { rect.top() } else { rect.bottom() };
+                            ui.painter().hline(rect.x_range(), line_y, ui.visuals().selection.stroke);
+                        }
+                        if let Some(from) = response.dnd_release_payload::<usize>() {
+                            if !read_only {
+                                item_to_move = Some((*from, insert_at));
                             }
                         }
 
                         // Draw background
                         if is_current {
                             ui.painter().rect_filled(rect, 2.0, ui.visuals().selection.bg_fill);
+                        } else if self.selected.contains(item) {
+                            ui.painter().rect_filled(rect, 2.0, ui.visuals().selection.bg_fill.gamma_multiply(0.5));
                         } else if is_hovered {
                             ui.painter().rect_filled(rect, 2.0, ui.visuals().widgets.hovered.bg_fill);
                         }
+                        if is_cursor {
+                            ui.painter().rect_stroke(rect, 2.0, ui.visuals().selection.stroke, egui::StrokeKind::Inside);
+                            if cursor_moved {
+                                response.scroll_to_me(Some(egui::Align::Center));
+                            }
+                        }
 
                         // Draw text
                         let text_color = if !exists {
@@ -1383,26 +4717,35 @@ impl eframe::App for MusicPlayerApp {
                         } else {
                             ui.visuals().text_color()
                         };
+                        paint_cell_text(ui, cells[0], &index.to_string(), text_color);
+                        paint_cell_text(ui, cells[2], &meta.artist, text_color);
+                        paint_cell_text(ui, cells[3], &meta.album, text_color);
+                        paint_cell_text(ui, cells[4], &format_track_duration(meta.duration_secs), text_color);
+                        paint_cell_text(ui, cells[5], &format_rating(&meta), text_color);
+                        paint_cell_text(ui, cells[6], &meta.play_count.to_string(), text_color);
+                        paint_cell_text(ui, cells[7], &format_last_played(meta.last_played, self.language), text_color);
+                        paint_cell_text(ui, cells[8], &item.path.to_string_lossy(), text_color);
                         
-                        // Use a child UI to clip and scroll
-                        let child_ui = ui.new_child(egui::UiBuilder::new().max_rect(rect).layout(*ui.layout()));
+                        // Use a child UI to clip and scroll the title
+                        let title_rect = title_rect.shrink2(egui::vec2(2.0, 0.0));
+                        let child_ui = ui.new_child(egui::UiBuilder::new().max_rect(title_rect).layout(*ui.layout()));
                         // We need to manually implement scrolling here because scroll_label allocates its own rect
                         // which might conflict or be easier to just inline.
                         
                         let font_id = egui::FontId::proportional(14.0);
-                        let text_galley = child_ui.painter().layout_no_wrap(text.clone(), font_id, text_color);
+                        let text_galley = child_ui.painter().layout_no_wrap(title, font_id, text_color);
                         let text_width = text_galley.size().x;
 
                         // When playback is paused, the app may not repaint continuously.
                         // For hover-scrolling labels, request a repaint while hovered so the animation keeps running.
-                        if is_hovered && text_width > rect.width() {
+                        if is_hovered && text_width > title_rect.width() {
                             ctx.request_repaint();
                         }
                         
-                        if text_width <= rect.width() || !is_hovered {
+                        if text_width <= title_rect.width() || !is_hovered {
                             // Center vertically
-                            let pos = rect.min + egui::vec2(0.0, (rect.height() - text_galley.size().y) / 2.0);
-                            child_ui.painter().galley(pos, text_galley, egui::Color32::TRANSPARENT);
+                            let pos = title_rect.min + egui::vec2(0.0, (title_rect.height() - text_galley.size().y) / 2.0);
+                            child_ui.painter().with_clip_rect(title_rect).galley(pos, text_galley, egui::Color32::TRANSPARENT);
                         } else {
                             let speed = 30.0; 
                             let gap = 50.0;
@@ -1410,8 +4753,8 @@ impl eframe::App for MusicPlayerApp {
                             let time = child_ui.input(|i| i.time);
                             let offset = (time * speed as f64) % cycle_len as f64;
                             
-                            child_ui.painter().with_clip_rect(rect).add(egui::Shape::Text(egui::epaint::TextShape {
-                                pos: rect.min + egui::vec2(-offset as f32, (rect.height() - text_galley.size().y) / 2.0),
+                            child_ui.painter().with_clip_rect(title_rect).add(egui::Shape::Text(egui::epaint::TextShape {
+                                pos: title_rect.min + egui::vec2(-offset as f32, (title_rect.height() - text_galley.size().y) / 2.0),
                                 galley: text_galley.clone(),
                                 underline: egui::Stroke::NONE,
                                 override_text_color: None,
@@ -1420,9 +4763,9 @@ impl eframe::App for MusicPlayerApp {
                                 opacity_factor: 1.0,
                             }));
                             
-                            if offset as f32 + rect.width() > text_width + gap {
-                                 child_ui.painter().with_clip_rect(rect).add(egui::Shape::Text(egui::epaint::TextShape {
-                                    pos: rect.min + egui::vec2(-offset as f32 + cycle_len as f32, (rect.height() - text_galley.size().y) / 2.0),
+                            if offset as f32 + title_rect.width() > text_width + gap {
+                                 child_ui.painter().with_clip_rect(title_rect).add(egui::Shape::Text(egui::epaint::TextShape {
+                                    pos: title_rect.min + egui::vec2(-offset as f32 + cycle_len as f32, (title_rect.height() - text_galley.size().y) / 2.0),
                                     galley: text_galley,
                                     underline: egui::Stroke::NONE,
                                     override_text_color: None,
@@ -1434,20 +4777,72 @@ impl eframe::App for MusicPlayerApp {
                         }
                         
                         if !exists {
-                            let not_exist_text = match self.language {
-                                Language::Chinese => format!("文件不存在: {:?}", item.path),
-                                Language::English => format!("File not found: {:?}", item.path),
-                            };
+                            let not_exist_text = self.language.trf("file_not_found", &[("path", &format!("{:?}", item.path))]);
                             response.clone().on_hover_text(not_exist_text);
                         }
 
                         response.context_menu(|ui| {
-                            let remove_label = match self.language {
-                                Language::Chinese => "从列表中删除",
-                                Language::English => "Remove from list",
+                            let count = self.selected.len();
+                            let remove_label = if count > 1 {
+                                self.language.trf("remove_selected", &[("n", &count)])
+                            } else {
+                                self.language.tr("remove_from_list").to_string()
                             };
-                            if ui.button(remove_label).clicked() {
-                                item_to_delete = Some(index);
+                            if !read_only && ui.button(remove_label).clicked() {
+                                bulk_action = Some(BulkAction::Remove);
+                                ui.close();
+                            }
+                            if !other_playlists.is_empty() {
+                                if !read_only {
+                                    ui.menu_button(self.language.tr("move_to"), |ui| {
+                                        for name in &other_playlists {
+                                            if ui.button(name).clicked() {
+                                                bulk_action = Some(BulkAction::MoveTo(name.clone()));
+                                                ui.close();
+                                            }
+                                        }
+                                    });
+                                }
+                                ui.menu_button(self.language.tr("copy_to"), |ui| {
+                                    for name in &other_playlists {
+                                        if ui.button(name).clicked() {
+                                            bulk_action = Some(BulkAction::CopyTo(name.clone()));
+                                            ui.close();
+                                        }
+                                    }
+                                });
+                            }
+                            if ui.button(self.language.tr("add_to_queue")).clicked() {
+                                bulk_action = Some(BulkAction::Queue);
+                                ui.close();
+                            }
+                            ui.menu_button(self.language.tr("rate"), |ui| {
+                                for stars in (0..=5u8).rev() {
+                                    let label = if stars == 0 {
+                                        self.language.tr("no_rating").to_string()
+                                    } else {
+                                        "★".repeat(stars as usize)
+                                    };
+                                    if ui.button(label).clicked() {
+                                        bulk_action = Some(BulkAction::Rate(stars));
+                                        ui.close();
+                                    }
+                                }
+                            });
+                            // Unfavourite only when everything selected already is a favourite
+                            let all_favourite = self.selected.iter()
+                                .all(|item| data.library.get(&item.path).is_some_and(|meta| meta.favourite));
+                            let favourite_label = self.language.tr(if all_favourite { "remove_from_favourites" } else { "add_to_favourites" });
+                            if ui.button(favourite_label).clicked() {
+                                bulk_action = Some(BulkAction::Favourite(!all_favourite));
+                                ui.close();
+                            }
+                            if ui.button(self.language.tr("reveal_in_file_manager")).clicked() {
+                                bulk_action = Some(BulkAction::Reveal);
+                                ui.close();
+                            }
+                            if ui.button(self.language.tr("edit_tags")).clicked() {
+                                bulk_action = Some(BulkAction::EditTags);
                                 ui.close();
                             }
                         });
@@ -1455,17 +4850,79 @@ impl eframe::App for MusicPlayerApp {
                 }
             });
             
-            if let Some(index) = item_to_delete {
-                let current_name = data.current_name.clone();
-                if let Some(list) = data.lists.get_mut(&current_name) {
-                    if index < list.len() {
-                        list.remove(index);
-                        data.save();
+            let mut to_rate: Option<(Vec<PathBuf>, Option<u8>, Option<bool>)> = None;
+            if let Some(action) = bulk_action {
+                let indices = selected_indices(&current_list, &self.selected);
+                match action {
+                    BulkAction::Remove => {
+                        data.remove_items(&current_name, &indices);
+                        self.selected.clear();
+                    }
+                    BulkAction::MoveTo(target) => {
+                        data.copy_items(&current_name, &indices, &target);
+                        data.remove_items(&current_name, &indices);
+                        self.selected.clear();
+                    }
+                    BulkAction::CopyTo(target) => {
+                        data.copy_items(&current_name, &indices, &target);
+                    }
+                    BulkAction::Queue => {
+                        data.queue_items(&current_name, &indices);
+                    }
+                    // Applied once the lock is released, since new tracks need their tags read
+                    BulkAction::Rate(stars) => {
+                        let paths = indices.iter().filter_map(|&i| current_list.get(i).map(|item| item.path.clone())).collect();
+                        to_rate = Some((paths, Some(stars), None));
+                    }
+                    BulkAction::Favourite(favourite) => {
+                        let paths = indices.iter().filter_map(|&i| current_list.get(i).map(|item| item.path.clone())).collect();
+                        to_rate = Some((paths, None, Some(favourite)));
+                    }
+                    BulkAction::EditTags => {
+                        let paths: Vec<PathBuf> = indices.iter()
+                            .filter_map(|&i| current_list.get(i))
+                            .filter(|item| item.path.exists())
+                            .map(|item| item.path.clone())
+                            .collect();
+                        if !paths.is_empty() {
+                            match TagEditor::open(paths) {
+                                Ok(editor) => self.tag_editor = Some(editor),
+                                Err(e) => self.error_message = Some(e),
+                            }
+                        }
+                    }
+                    BulkAction::Reveal => {
+                        // One window per folder is enough
+                        let mut folders = Vec::new();
+                        for item in indices.iter().filter_map(|&i| current_list.get(i)) {
+                            let folder = item.path.parent().map(Path::to_path_buf);
+                            if !folders.contains(&folder) {
+                                folders.push(folder);
+                                reveal_in_file_manager(&item.path);
+                            }
+                        }
                     }
                 }
+                data.save();
+            }
+
+            if let Some((from, insert_at)) = item_to_move {
+                data.move_item(&current_name, from, insert_at);
+                // A manual order replaces any persistent sort
+                data.sort_orders.remove(&current_name);
+                self.last_sort = None;
+                data.save();
             }
             
             drop(data); // Release lock
+
+            if let Some((paths, rating, favourite)) = to_rate {
+                let fresh = read_unindexed_meta(&self.data, &paths);
+                let mut data = self.data.lock().unwrap();
+                let changed = data.rate_tracks(&paths, fresh, rating, favourite);
+                spawn_rating_tag_writes(&data, changed, self.write_rating_tags.load(Ordering::Relaxed));
+                data.save();
+            }
             
             if let Some(path) = file_to_play {
                 self.play_file(path);
@@ -1473,10 +4930,7 @@ impl eframe::App for MusicPlayerApp {
         });
 
         if self.show_duplicate_dialog {
-            let title = match self.language {
-                Language::Chinese => "发现同名文件",
-                Language::English => "Duplicate Files Found",
-            };
+            let title = self.language.tr("duplicate_files_found");
             let mut open = true;
             let mut should_close = false;
             egui::Window::new(title)
@@ -1484,38 +4938,27 @@ impl eframe::App for MusicPlayerApp {
                 .resizable(false)
                 .open(&mut open)
                 .show(ctx, |ui| {
-                    let msg = match self.language {
-                        Language::Chinese => format!("发现 {} 个同名文件，是否重命名并添加？", self.pending_files.len()),
-                        Language::English => format!("Found {} duplicate files. Rename and add?", self.pending_files.len()),
-                    };
+                    let msg = self.language.trf("duplicates_found", &[("n", &self.pending_files.len())]);
                     ui.label(msg);
                     ui.horizontal(|ui| {
-                        let add_rename_label = match self.language {
-                            Language::Chinese => "添加并重命名",
-                            Language::English => "Add & Rename",
-                        };
+                        let add_rename_label = self.language.tr("add_rename");
                         if ui.button(add_rename_label).clicked() {
                             let mut data = self.data.lock().unwrap();
                             let current_name = data.current_name.clone();
-                            let list = data.lists.entry(current_name).or_default();
+                            let list = data.lists.entry(current_name.clone()).or_default();
                             
                             for path in self.pending_files.drain(..) {
                                 let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-                                let mut final_name = name.clone();
-                                let mut count = 1;
-                                while list.iter().any(|item| item.name == final_name) {
-                                    final_name = format!("{} ({})", name, count);
-                                    count += 1;
-                                }
+                                let final_name = unique_item_name(list, &name);
                                 list.insert(0, PlaylistItem { path, name: final_name });
                             }
+                            data.apply_sort(&current_name);
                             data.save();
+                            drop(data);
+                            spawn_library_indexer(self.data.clone());
                             should_close = true;
                         }
-                        let cancel_label = match self.language {
-                            Language::Chinese => "取消",
-                            Language::English => "Cancel",
-                        };
+                        let cancel_label = self.language.tr("cancel");
                         if ui.button(cancel_label).clicked() {
                             self.pending_files.clear();
                             should_close = true;
@@ -1528,96 +4971,212 @@ impl eframe::App for MusicPlayerApp {
         }
 
         if self.show_lyrics_settings {
-            let title = match self.language {
-                Language::Chinese => "歌词设置",
-                Language::English => "Lyrics Settings",
-            };
+            // Taken out while the window borrows self, put back below
+            let system_fonts = self.system_fonts.take().unwrap_or_else(fonts::list_system_fonts);
+            let (ui_font, lyrics_font) = (self.ui_font.clone(), self.lyrics_font.clone());
+            let title = self.language.tr("lyrics_settings");
             let mut open = true;
             let mut should_close = false;
             egui::Window::new(title)
                 .collapsible(false)
                 .resizable(true)
                 .default_size([300.0, 300.0])
-                .frame(egui::Frame::window(ctx.style().as_ref()).fill(self.ui_bg_color))
+                .frame(egui::Frame::window(ctx.style().as_ref()).fill(self.theme.background()))
                 .open(&mut open)
                 .show(ctx, |ui| {
-                    let mut state = self.lyrics_state.lock().unwrap();
+                    // Through a clone of the Arc, so the theme section can borrow self
+                    let lyrics_state = self.lyrics_state.clone();
+                    let mut state = lyrics_state.lock().unwrap();
                     egui::ScrollArea::vertical().show(ui, |ui| {
-                        ui.heading(match self.language {
-                            Language::Chinese => "歌词设置",
-                            Language::English => "Lyrics Settings",
-                        });
+                        ui.heading(self.language.tr("lyrics_settings"));
                         egui::Grid::new("lyrics_settings_grid").num_columns(2).show(ui, |ui| {
-                            ui.label(match self.language {
-                                Language::Chinese => "字体大小",
-                                Language::English => "Font Size",
-                            });
+                            ui.label(self.language.tr("lyrics_font"));
+                            font_picker(ui, "lyrics_font", &mut self.lyrics_font, &system_fonts, self.language.tr("lyrics_font_same_as_ui"), self.language);
+                            ui.end_row();
+
+                            ui.label(self.language.tr("font_size"));
                             ui.add(egui::Slider::new(&mut state.font_size, 10.0..=100.0));
                             ui.end_row();
 
-                            ui.label(match self.language {
-                                Language::Chinese => "字体颜色",
-                                Language::English => "Text Color",
-                            });
+                            ui.label(self.language.tr("text_color"));
                             ui.color_edit_button_srgba(&mut state.text_color);
                             ui.end_row();
 
-                            ui.label(match self.language {
-                                Language::Chinese => "背景颜色",
-                                Language::English => "Background Color",
-                            });
+                            ui.label(self.language.tr("highlight_color"));
+                            ui.color_edit_button_srgba(&mut state.highlight_color);
+                            ui.end_row();
+
+                            ui.label(self.language.tr("translation_font_size"));
+                            ui.add(egui::Slider::new(&mut state.translation_font_size, 10.0..=100.0));
+                            ui.end_row();
+
+                            ui.label(self.language.tr("translation_color"));
+                            ui.color_edit_button_srgba(&mut state.translation_color);
+                            ui.end_row();
+
+                            ui.label(self.language.tr("background_color"));
                             ui.color_edit_button_srgba(&mut state.bg_color);
                             ui.end_row();
                         });
 
                         ui.separator();
-                        
-                        ui.heading(match self.language {
-                            Language::Chinese => "界面设置",
-                            Language::English => "UI Settings",
-                        });
-                        ui.label(match self.language {
-                            Language::Chinese => "主界面背景",
-                            Language::English => "Main UI Background",
+
+                        ui.heading(self.language.tr("desktop_lyrics"));
+                        egui::Grid::new("desktop_lyrics_grid").num_columns(2).show(ui, |ui| {
+                            ui.label(self.language.tr("alignment"));
+                            egui::ComboBox::from_id_salt("desktop_lyrics_align")
+                                .selected_text(state.align.as_str(self.language))
+                                .show_ui(ui, |ui| {
+                                    for align in LyricsAlign::ALL {
+                                        ui.selectable_value(&mut state.align, align, align.as_str(self.language));
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label(self.language.tr("effect_color"));
+                            ui.color_edit_button_srgba(&mut state.effect_color);
+                            ui.end_row();
                         });
-                        ui.color_edit_button_srgba(&mut self.ui_bg_color);
+                        ui.checkbox(&mut state.outline, self.language.tr("text_outline"));
+                        ui.checkbox(&mut state.shadow, self.language.tr("text_shadow"));
+                        ui.checkbox(&mut state.two_lines, self.language.tr("desktop_lyrics_two_lines"));
+                        ui.checkbox(&mut state.locked, self.language.tr("desktop_lyrics_locked"));
+
+                        ui.separator();
+
+                        ui.heading(self.language.tr("lyrics_sources"));
+                        ui.label(self.language.tr("lyrics_sources_hint"));
+                        let mut move_source = None;
+                        let source_count = self.lyrics_sources.len();
+                        for (i, source) in self.lyrics_sources.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(i > 0, egui::Button::new("⬆")).clicked() {
+                                    move_source = Some((i, i - 1));
+                                }
+                                if ui.add_enabled(i + 1 < source_count, egui::Button::new("⬇")).clicked() {
+                                    move_source = Some((i, i + 1));
+                                }
+                                ui.label(format!("{}. {}", i + 1, source.as_str(self.language)));
+                            });
+                        }
+                        if let Some((a, b)) = move_source {
+                            self.lyrics_sources.swap(a, b);
+                        }
+
+                        ui.label(self.language.tr("lyrics_folders"));
+                        let mut remove_dir = None;
+                        for (i, dir) in self.lyrics_dirs.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("✖").clicked() {
+                                    remove_dir = Some(i);
+                                }
+                                ui.label(dir.to_string_lossy());
+                            });
+                        }
+                        if let Some(i) = remove_dir {
+                            self.lyrics_dirs.remove(i);
+                        }
+                        if ui.button(self.language.tr("add_folder")).clicked() {
+                            if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                if !self.lyrics_dirs.contains(&dir) {
+                                    self.lyrics_dirs.push(dir);
+                                }
+                            }
+                        }
+                        // The HTTP API looks lyrics up the same way
+                        *self.lyrics_lookup.lock().unwrap() = LyricsLookup { sources: self.lyrics_sources.clone(), dirs: self.lyrics_dirs.clone() };
+
+                        ui.separator();
+                        
+                        ui.heading(self.language.tr("ui_settings"));
+                        ui.label(self.language.tr("ui_font"));
+                        font_picker(ui, "ui_font", &mut self.ui_font, &system_fonts, self.language.tr("ui_font_auto"), self.language);
+                        let mut write_rating_tags = self.write_rating_tags.load(Ordering::Relaxed);
+                        if ui.checkbox(&mut write_rating_tags, self.language.tr("write_ratings_to_file_tags")).changed() {
+                            self.write_rating_tags.store(write_rating_tags, Ordering::Relaxed);
+                        }
+
+                        ui.separator();
+
+                        ui.heading(self.language.tr("theme"));
+                        self.show_theme_settings(ui);
+
+                        ui.separator();
+
+                        ui.heading(self.language.tr("shortcuts"));
+                        self.show_shortcut_settings(ui);
 
                         ui.add_space(10.0);
 
                         ui.horizontal(|ui| {
-                            if ui.button(match self.language {
-                                Language::Chinese => "保存设置",
-                                Language::English => "Save Settings",
-                            }).clicked() {
+                            if ui.button(self.language.tr("save_settings")).clicked() {
                                 let mut config = AppConfig::load();
-                                config.lyrics_font_size = state.font_size;
-                                config.lyrics_text_color = state.text_color.to_array();
-                                config.lyrics_bg_color = state.bg_color.to_array();
-                                config.ui_bg_color = self.ui_bg_color.to_array();
-                                if let Ok(file) = File::create(get_config_path("config.json")) {
-                                    let _ = serde_json::to_writer_pretty(file, &config);
-                                }
+                                state.store(&mut config);
+                                config.theme = self.theme.clone();
+                                config.themes = self.themes.clone();
+                                config.write_rating_tags = self.write_rating_tags.load(Ordering::Relaxed);
+                                config.lyrics_sources = self.lyrics_sources.clone();
+                                config.lyrics_dirs = self.lyrics_dirs.clone();
+                                config.ui_font = self.ui_font.clone();
+                                config.lyrics_font = self.lyrics_font.clone();
+                                config.shortcuts = self.shortcuts.clone();
+                                config.save();
                                 should_close = true;
                             }
-                            if ui.button(match self.language {
-                                Language::Chinese => "关闭",
-                                Language::English => "Close",
-                            }).clicked() {
+                            if ui.button(self.language.tr("close")).clicked() {
                                 should_close = true;
                             }
                         });
                     });
                 });
+            self.system_fonts = Some(system_fonts);
             if !open || should_close {
                 self.show_lyrics_settings = false;
+                self.capturing_shortcut = None;
+            }
+            // Fonts apply right away; saving makes them stick
+            if self.ui_font != ui_font || self.lyrics_font != lyrics_font {
+                let errors = fonts::install(ctx, self.ui_font.as_ref(), self.lyrics_font.as_ref());
+                if !errors.is_empty() {
+                    self.error_message = Some(errors.join("\n"));
+                }
+            }
+        }
+
+        if self.smart_editor.is_some() {
+            self.show_smart_editor(ctx);
+        }
+
+        if self.tag_editor.is_some() {
+            self.show_tag_editor(ctx);
+        }
+
+        if self.lyrics_editor.is_some() {
+            self.show_lyrics_editor(ctx, display_pos);
+        }
+
+        if let Some(message) = self.error_message.clone() {
+            let title = self.language.tr("error");
+            let mut open = true;
+            let mut should_close = false;
+            egui::Window::new(title)
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label(message);
+                    let ok_label = self.language.tr("ok");
+                    if ui.button(ok_label).clicked() {
+                        should_close = true;
+                    }
+                });
+            if !open || should_close {
+                self.error_message = None;
             }
         }
 
         if self.show_rename_dialog {
-            let title = match self.language {
-                Language::Chinese => "重命名歌单",
-                Language::English => "Rename Playlist",
-            };
+            let title = self.language.tr("rename_playlist");
             let mut open = true;
             let mut should_close = false;
             egui::Window::new(title)
@@ -1627,10 +5186,7 @@ impl eframe::App for MusicPlayerApp {
                 .show(ctx, |ui| {
                     ui.text_edit_singleline(&mut self.rename_playlist_name);
                     ui.horizontal(|ui| {
-                        let ok_label = match self.language {
-                            Language::Chinese => "确定",
-                            Language::English => "OK",
-                        };
+                        let ok_label = self.language.tr("ok");
                         if ui.button(ok_label).clicked() {
                             if !self.rename_playlist_name.is_empty() {
                                 let mut data = self.data.lock().unwrap();
@@ -1638,6 +5194,12 @@ impl eframe::App for MusicPlayerApp {
                                 if !data.lists.contains_key(&self.rename_playlist_name) {
                                     if let Some(list) = data.lists.remove(&old_name) {
                                         data.lists.insert(self.rename_playlist_name.clone(), list);
+                                        if let Some(sort) = data.sort_orders.remove(&old_name) {
+                                            data.sort_orders.insert(self.rename_playlist_name.clone(), sort);
+                                        }
+                                        if let Some(smart) = data.smart.remove(&old_name) {
+                                            data.smart.insert(self.rename_playlist_name.clone(), smart);
+                                        }
                                         data.current_name = self.rename_playlist_name.clone();
                                         data.save();
                                     }
@@ -1645,10 +5207,7 @@ impl eframe::App for MusicPlayerApp {
                             }
                             should_close = true;
                         }
-                        let cancel_label = match self.language {
-                            Language::Chinese => "取消",
-                            Language::English => "Cancel",
-                        };
+                        let cancel_label = self.language.tr("cancel");
                         if ui.button(cancel_label).clicked() {
                             should_close = true;
                         }
@@ -1660,10 +5219,7 @@ impl eframe::App for MusicPlayerApp {
         }
 
         if self.show_delete_playlist_dialog {
-             let title = match self.language {
-                 Language::Chinese => "确认删除",
-                 Language::English => "Confirm Delete",
-             };
+             let title = self.language.tr("confirm_delete");
              let mut open = true;
              let mut should_close = false;
              egui::Window::new(title)
@@ -1672,21 +5228,17 @@ impl eframe::App for MusicPlayerApp {
                 .open(&mut open)
                 .show(ctx, |ui| {
                     if let Some(name) = &self.playlist_to_delete {
-                        let msg = match self.language {
-                            Language::Chinese => format!("确定要删除歌单 '{}' 吗？", name),
-                            Language::English => format!("Are you sure you want to delete playlist '{}'?", name),
-                        };
+                        let msg = self.language.trf("confirm_delete_playlist", &[("playlist", name)]);
                         ui.label(msg);
                     }
                     ui.horizontal(|ui| {
-                        let ok_label = match self.language {
-                            Language::Chinese => "确定",
-                            Language::English => "OK",
-                        };
+                        let ok_label = self.language.tr("ok");
                         if ui.button(ok_label).clicked() {
                             if let Some(name) = &self.playlist_to_delete {
                                 let mut data = self.data.lock().unwrap();
                                 data.lists.remove(name);
+                                data.sort_orders.remove(name);
+                                data.smart.remove(name);
                                 // If we deleted the current one, switch to another
                                 if data.current_name == *name {
                                     if let Some(first) = data.lists.keys().next().cloned() {
@@ -1701,10 +5253,7 @@ impl eframe::App for MusicPlayerApp {
                             should_close = true;
                             self.playlist_to_delete = None;
                         }
-                        let cancel_label = match self.language {
-                            Language::Chinese => "取消",
-                            Language::English => "Cancel",
-                        };
+                        let cancel_label = self.language.tr("cancel");
                         if ui.button(cancel_label).clicked() {
                             should_close = true;
                             self.playlist_to_delete = None;
@@ -1715,12 +5264,19 @@ impl eframe::App for MusicPlayerApp {
                 self.show_delete_playlist_dialog = false;
             }
         }
+
+        // Last, so open editors and text fields get their keys first
+        self.handle_shortcuts(ctx);
     }
 }
 
 fn main() -> eframe::Result<()> {
     // 1. Load Config
-    let config = AppConfig::load();
+    paths::init(paths::config_dir_arg());
+    paths::migrate_legacy_files();
+    let mut load_errors = i18n::init();
+    let (config, config_error) = AppConfig::load_with_error();
+    load_errors.extend(config_error);
     let port = config.port;
     let (port_tx, mut port_rx) = mpsc::unbounded_channel::<u16>();
 
@@ -1728,13 +5284,21 @@ fn main() -> eframe::Result<()> {
     let (audio_tx, audio_rx) = start_audio_thread();
 
     // 3. Shared State (Load from file)
-    let playlists_manager = PlaylistsManager::load();
+    let (mut playlists_manager, playlists_error) = PlaylistsManager::load();
+    load_errors.extend(playlists_error);
+    playlists_manager.refresh_smart_playlists();
     let data = Arc::new(Mutex::new(playlists_manager));
+    spawn_library_pruner(data.clone());
+    spawn_library_indexer(data.clone());
     
     let app_state = AppState {
-        audio_tx: audio_tx.clone(),
-        data: data.clone(),
+        audio_tx,
+        data,
+        now_playing: Arc::new(Mutex::new(NowPlaying::default())),
+        write_rating_tags: Arc::new(AtomicBool::new(config.write_rating_tags)),
+        lyrics_lookup: Arc::new(Mutex::new(LyricsLookup { sources: config.lyrics_sources.clone(), dirs: config.lyrics_dirs.clone() })),
     };
+    let ui_state = app_state.clone();
 
     // 4. Start API Server in a separate thread
     thread::spawn(move || {
@@ -1749,6 +5313,23 @@ fn main() -> eframe::Result<()> {
                     .route("/playlist/rename", post(api_rename_playlist))
                     .route("/playlist/delete", post(api_delete_playlist))
                     .route("/playlist/switch", post(api_switch_playlist))
+                    .route("/playlist/remove_many", post(api_remove_many))
+                    .route("/playlist/move", post(api_move_items))
+                    .route("/playlist/copy", post(api_copy_items))
+                    .route("/playlist/smart", post(api_save_smart_playlist))
+                    .route("/playlist/import", post(api_import_playlist))
+                    .route("/playlist/export", post(api_export_playlist))
+                    .route("/library/export", get(api_export_library))
+                    .route("/library/import", post(api_import_library))
+                    .route("/queue", get(api_get_queue))
+                    .route("/queue/add", post(api_queue_add))
+                    .route("/search", get(api_search))
+                    .route("/rate", post(api_rate))
+                    .route("/tags", get(api_get_tags).post(api_write_tags))
+                    .route("/history", get(api_get_history))
+                    .route("/stats/top", get(api_stats_top))
+                    .route("/lyrics", get(api_get_lyrics).put(api_put_lyrics))
+                    .route("/lyrics/current", get(api_current_lyrics))
                     .with_state(app_state.clone());
 
                 let addr = format!("0.0.0.0:{}", active_port);
@@ -1789,7 +5370,7 @@ fn main() -> eframe::Result<()> {
     // 5. Run UI
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
-            .with_inner_size([400.0, 600.0])
+            .with_inner_size([560.0, 600.0])
             .with_transparent(true),
         ..Default::default()
     };
@@ -1797,6 +5378,197 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "Music Player",
         options,
-        Box::new(move |cc| Ok(Box::new(MusicPlayerApp::new(audio_tx, audio_rx, data, port, port_tx, cc)))),
+        Box::new(move |cc| Ok(Box::new(MusicPlayerApp::new(ui_state, audio_rx, port, port_tx, load_errors, cc)))),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str) -> PlaylistItem {
+        PlaylistItem { path: PathBuf::from(format!("/music/{}.mp3", name)), name: format!("{}.mp3", name) }
+    }
+
+    fn titled(title: &str) -> TrackMeta {
+        TrackMeta { title: title.to_string(), ..Default::default() }
+    }
+
+    // A manager with one list "A" holding the given file names in order
+    fn manager(names: &[&str]) -> PlaylistsManager {
+        let mut data = PlaylistsManager::default();
+        data.lists.insert("A".to_string(), names.iter().map(|name| item(name)).collect());
+        data
+    }
+
+    fn names(data: &PlaylistsManager, list: &str) -> Vec<String> {
+        data.lists[list].iter().map(|item| item.name.trim_end_matches(".mp3").to_string()).collect()
+    }
+
+    #[test]
+    fn test_migrate_config_moves_bg_color_into_theme() {
+        let custom = serde_json::json!({"version": 2, "port": 8080, "ui_bg_color": [10, 20, 30, 255]});
+        let config: AppConfig = storage::from_versioned_value(custom, CONFIG_VERSION, migrate_config).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.theme.name, "Custom");
+        assert_eq!(config.theme.background, [10, 20, 30, 255]);
+
+        // The old default colour just means the dark theme
+        let default_bg = Theme::dark().background;
+        let migrated = migrate_config(serde_json::json!({"port": 8080, "ui_bg_color": default_bg}), 2);
+        assert!(migrated.get("ui_bg_color").is_none());
+        let theme: Theme = serde_json::from_value(migrated["theme"].clone()).unwrap();
+        assert_eq!(theme, Theme::dark());
+    }
+
+    #[test]
+    fn test_migrate_playlists_from_v1() {
+        let v1 = serde_json::json!({"current_name": "Default", "lists": {"Default": []}});
+        let migrated = migrate_playlists(v1, 1);
+        assert_eq!(migrated["version"], PLAYLISTS_VERSION);
+        assert_eq!(migrated["queue"], serde_json::json!([]));
+        assert_eq!(migrated["history"], serde_json::json!([]));
+        let data: PlaylistsManager = serde_json::from_value(migrated).unwrap();
+        assert_eq!(data.current_name, "Default");
+        assert!(data.library.is_empty() && data.smart.is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_substring_beats_subsequence() {
+        let substring = fuzzy_score("love", "Glove Box").unwrap();
+        let subsequence = fuzzy_score("love", "Long Over Everything").unwrap();
+        assert!(substring > subsequence);
+        assert_eq!(fuzzy_score("xyz", "Glove Box"), None);
+    }
+
+    #[test]
+    fn test_fuzzy_word_start_bonus() {
+        assert!(fuzzy_score("box", "Glove Box").unwrap() > fuzzy_score("box", "Jukeboxes").unwrap());
+        // Subsequences score word starts too
+        assert!(fuzzy_score("gb", "Glove Box").unwrap() > fuzzy_score("gb", "Bigger Bob").unwrap());
+    }
+
+    #[test]
+    fn test_fuzzy_every_term_must_match() {
+        assert!(fuzzy_score("BEATLES help", "The Beatles - Help!").is_some());
+        assert_eq!(fuzzy_score("beatles yesterday", "The Beatles - Help!"), None);
+        let one = fuzzy_score("beatles", "The Beatles - Help!").unwrap();
+        assert!(fuzzy_score("beatles help", "The Beatles - Help!").unwrap() > one);
+    }
+
+    #[test]
+    fn test_fuzzy_empty_query() {
+        assert_eq!(fuzzy_score("", "Anything"), None);
+        assert_eq!(fuzzy_score("   ", "Anything"), None);
+    }
+
+    #[test]
+    fn test_sort_list_by_title() {
+        let mut data = manager(&["c", "a", "b"]);
+        data.library.insert(item("c").path, titled("A"));
+        // Untitled tracks sort by their name
+        data.sort_list("A", PlaylistSort { column: SortColumn::Title, descending: false });
+        assert_eq!(names(&data, "A"), ["c", "a", "b"]);
+        data.sort_list("A", PlaylistSort { column: SortColumn::Title, descending: true });
+        assert_eq!(names(&data, "A"), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_sort_list_by_rating_puts_favourites_first() {
+        let mut data = manager(&["a", "b", "c"]);
+        data.library.insert(item("a").path, TrackMeta { rating: 4, ..Default::default() });
+        data.library.insert(item("b").path, TrackMeta { rating: 4, favourite: true, ..Default::default() });
+        data.library.insert(item("c").path, TrackMeta { rating: 5, ..Default::default() });
+        data.sort_list("A", PlaylistSort { column: SortColumn::Rating, descending: true });
+        assert_eq!(names(&data, "A"), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_move_item() {
+        let mut data = manager(&["a", "b", "c", "d"]);
+        // In front of "d", i.e. after "c"
+        data.move_item("A", 0, 3);
+        assert_eq!(names(&data, "A"), ["b", "c", "a", "d"]);
+        data.move_item("A", 3, 0);
+        assert_eq!(names(&data, "A"), ["d", "b", "c", "a"]);
+        // To the end
+        data.move_item("A", 0, 4);
+        assert_eq!(names(&data, "A"), ["b", "c", "a", "d"]);
+        // Out of bounds is ignored
+        data.move_item("A", 4, 0);
+        data.move_item("A", 0, 5);
+        assert_eq!(names(&data, "A"), ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn test_kept_sorted_lists_stay_sorted() {
+        let mut data = manager(&["b", "d"]);
+        data.lists.insert("B".to_string(), vec![item("c"), item("a")]);
+        data.sort_orders.insert("A".to_string(), PlaylistSort { column: SortColumn::Path, descending: false });
+        assert_eq!(data.copy_items("B", &[0, 1], "A"), 2);
+        assert_eq!(names(&data, "A"), ["a", "b", "c", "d"]);
+        data.import_playlist(vec![item("e").path, item("0").path], Some("A".to_string()), "").unwrap();
+        assert_eq!(names(&data, "A"), ["0", "a", "b", "c", "d", "e"]);
+
+        // Without a sort order copies go to the top
+        assert_eq!(data.copy_items("A", &[5], "B"), 1);
+        assert_eq!(names(&data, "B"), ["e", "c", "a"]);
+    }
+
+    #[test]
+    fn test_remove_items() {
+        let mut data = manager(&["a", "b", "c", "d"]);
+        // Duplicates and out of bounds indices are ignored
+        assert_eq!(data.remove_items("A", &[3, 1, 1, 9]), 2);
+        assert_eq!(names(&data, "A"), ["a", "c"]);
+        assert_eq!(data.remove_items("missing", &[0]), 0);
+        data.library.insert(item("a").path, TrackMeta { favourite: true, ..Default::default() });
+        data.refresh_smart_playlists();
+        assert_eq!(data.remove_items(FAVOURITES_PLAYLIST, &[0]), 0);
+        assert_eq!(names(&data, FAVOURITES_PLAYLIST), ["a"]);
+    }
+
+    #[test]
+    fn test_copy_items() {
+        let mut data = manager(&["a", "b", "c"]);
+        let other = PlaylistItem { path: PathBuf::from("/other/a.mp3"), name: "a.mp3".to_string() };
+        data.lists.insert("B".to_string(), vec![item("b"), other]);
+        // "b" is already there; the other "a.mp3" gets a unique name
+        assert_eq!(data.copy_items("A", &[0, 1, 2, 7], "B"), 2);
+        assert_eq!(names(&data, "B"), ["a.mp3 (1)", "c", "b", "a"]);
+        // New lists are created, smart ones are read-only
+        assert_eq!(data.copy_items("A", &[2], "New"), 1);
+        assert_eq!(names(&data, "New"), ["c"]);
+        data.refresh_smart_playlists();
+        assert_eq!(data.copy_items("A", &[0], FAVOURITES_PLAYLIST), 0);
+    }
+
+    #[test]
+    fn test_queue_items() {
+        let mut data = manager(&["a", "b", "c"]);
+        assert_eq!(data.queue_items("A", &[2, 0, 5]), 2);
+        assert_eq!(data.queue, vec![item("c").path, item("a").path]);
+    }
+
+    #[test]
+    fn test_selection_follows_items_when_sorted() {
+        let mut data = manager(&["c", "a", "b", "d"]);
+        let selected = HashSet::from([item("c"), item("b")]);
+        data.sort_list("A", PlaylistSort { column: SortColumn::Path, descending: false });
+        let indices = selected_indices(&data.lists["A"], &selected);
+        assert_eq!(indices, [1, 2]);
+        data.remove_items("A", &indices);
+        assert_eq!(names(&data, "A"), ["a", "d"]);
+    }
+
+    #[test]
+    fn test_selection_follows_items_when_moved() {
+        let mut data = manager(&["a", "b", "c"]);
+        let selected = HashSet::from([item("a")]);
+        data.move_item("A", 0, 3);
+        assert_eq!(selected_indices(&data.lists["A"], &selected), [2]);
+        // Items no longer in the list are simply not selected
+        data.remove_items("A", &[2]);
+        assert!(selected_indices(&data.lists["A"], &selected).is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+// --- MPRIS ---
+
+use super::shortcuts::{Action, MediaRequest};
+use crossbeam_channel::Sender;
+use eframe::egui;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zbus::blocking::connection;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{fdo, interface};
+
+// The desktop forwards media keys to players that own a name like this
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.rweb_music_player";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+// Track id for "nothing loaded", defined by the spec
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+impl PlaybackStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+    }
+}
+
+/// The loaded track as clients see it.
+struct Track {
+    // Changes with every track, so SetPosition calls for an old one are ignored
+    id: u64,
+    path: PathBuf,
+    title: String,
+    artist: String,
+    album: String,
+    length: Duration,
+}
+
+impl Track {
+    // The id is set once the track is published
+    fn read(path: &Path) -> Self {
+        let meta = super::read_track_meta(path);
+        let title = if meta.title.is_empty() {
+            path.file_stem().unwrap_or_default().to_string_lossy().to_string()
+        } else {
+            meta.title
+        };
+        Self { id: 0, path: path.to_path_buf(), title, artist: meta.artist, album: meta.album, length: Duration::ZERO }
+    }
+
+    fn object_path(&self) -> String {
+        format!("/org/mpris/MediaPlayer2/Track/{}", self.id)
+    }
+}
+
+/// What the UI last published, shared with the D-Bus interfaces.
+#[derive(Default)]
+struct State {
+    track: Option<Track>,
+    status: PlaybackStatus,
+    position: Duration,
+    synced_at: Option<Instant>,
+    volume: f64,
+    last_track_id: u64,
+}
+
+impl State {
+    // Clients read Position whenever they like, so it's interpolated like the UI does
+    fn position(&self) -> Duration {
+        match (self.status, self.synced_at) {
+            (PlaybackStatus::Playing, Some(synced_at)) => {
+                let position = self.position + synced_at.elapsed();
+                let length = self.track.as_ref().map_or(Duration::ZERO, |t| t.length);
+                if length > Duration::ZERO { position.min(length) } else { position }
+            }
+            _ => self.position,
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let Some(track) = &self.track else {
+            metadata.insert("mpris:trackid".to_string(), owned(ObjectPath::from_static_str_unchecked(NO_TRACK)));
+            return metadata;
+        };
+        metadata.insert("mpris:trackid".to_string(), owned(ObjectPath::from_string_unchecked(track.object_path())));
+        if track.length > Duration::ZERO {
+            metadata.insert("mpris:length".to_string(), owned(micros(track.length)));
+        }
+        metadata.insert("xesam:title".to_string(), owned(track.title.clone()));
+        if !track.artist.is_empty() {
+            metadata.insert("xesam:artist".to_string(), owned(vec![track.artist.clone()]));
+        }
+        if !track.album.is_empty() {
+            metadata.insert("xesam:album".to_string(), owned(track.album.clone()));
+        }
+        metadata.insert("xesam:url".to_string(), owned(file_url(&track.path)));
+        metadata
+    }
+}
+
+fn owned<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+    // Only values holding file descriptors can fail to convert
+    value.into().try_to_owned().expect("metadata holds no file descriptors")
+}
+
+fn micros(duration: Duration) -> i64 {
+    duration.as_micros().min(i64::MAX as u128) as i64
+}
+
+// file:// URL with everything but unreserved characters and '/' percent-encoded
+fn file_url(path: &Path) -> String {
+    let mut url = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
+
+/// The player's MPRIS service. The UI publishes its state with `update` every
+/// frame; calls from the desktop (media keys, panel applets, `playerctl`)
+/// arrive as requests on the UI thread, which turns them into audio commands.
+pub struct Mpris {
+    connection: zbus::blocking::Connection,
+    state: Arc<Mutex<State>>,
+}
+
+impl Mpris {
+    /// Registers on the session bus.
+    pub fn start(requests: Sender<MediaRequest>, ctx: egui::Context) -> zbus::Result<Self> {
+        Self::serve(connection::Builder::session()?.name(BUS_NAME)?, requests, ctx)
+    }
+
+    /// Serves the interfaces on the connection `builder` makes, which can be a
+    /// private bus or a peer-to-peer connection.
+    fn serve(builder: connection::Builder<'_>, requests: Sender<MediaRequest>, ctx: egui::Context) -> zbus::Result<Self> {
+        let state = Arc::new(Mutex::new(State { volume: 1.0, ..State::default() }));
+        let player = Player { requests, ctx, state: state.clone() };
+        let connection = builder
+            .serve_at(OBJECT_PATH, Root)?
+            .serve_at(OBJECT_PATH, player)?
+            .build()?;
+        Ok(Self { connection, state })
+    }
+
+    /// Publishes the playing track, status, position and volume. Clients are
+    /// sent PropertiesChanged for what changed, and Seeked when the position
+    /// jumps rather than moving on with playback.
+    pub fn update(&self, path: Option<&Path>, status: PlaybackStatus, position: Duration, length: Duration, volume: f32) {
+        let mut changed: HashMap<&str, Value> = HashMap::new();
+        let mut seeked = None;
+        // Tags are read before taking the lock, so D-Bus calls don't wait on the disk.
+        // Only the UI calls this, so the track can't change in between.
+        let track_changed = self.state.lock().unwrap().track.as_ref().map(|t| t.path.as_path()) != path;
+        let new_track = if track_changed { path.map(Track::read) } else { None };
+        {
+            let mut state = self.state.lock().unwrap();
+            if track_changed {
+                state.last_track_id += 1;
+                let id = state.last_track_id;
+                state.track = new_track.map(|track| Track { id, ..track });
+                changed.insert("CanSeek", state.track.is_some().into());
+            } else if state.track.is_some() && position.abs_diff(state.position()) > Duration::from_secs(1) {
+                seeked = Some(position);
+            }
+            // The length arrives with the first status update after the track starts
+            let length_changed = state.track.as_mut().is_some_and(|track| std::mem::replace(&mut track.length, length) != length);
+            if track_changed || length_changed {
+                changed.insert("Metadata", state.metadata().into());
+            }
+            if state.status != status {
+                state.status = status;
+                changed.insert("PlaybackStatus", status.as_str().into());
+            }
+            if (state.volume - volume as f64).abs() > 0.001 {
+                state.volume = volume as f64;
+                changed.insert("Volume", state.volume.into());
+            }
+            state.position = position;
+            state.synced_at = Some(Instant::now());
+        }
+
+        if !changed.is_empty() {
+            let body = (PLAYER_INTERFACE, changed, Vec::<&str>::new());
+            let _ = self.connection.emit_signal(None::<&str>, OBJECT_PATH, "org.freedesktop.DBus.Properties", "PropertiesChanged", &body);
+        }
+        if let Some(position) = seeked {
+            let _ = self.connection.emit_signal(None::<&str>, OBJECT_PATH, PLAYER_INTERFACE, "Seeked", &(micros(position),));
+        }
+    }
+}
+
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "Rweb Music Player".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    requests: Sender<MediaRequest>,
+    ctx: egui::Context,
+    state: Arc<Mutex<State>>,
+}
+
+impl Player {
+    fn send(&self, request: MediaRequest) {
+        let _ = self.requests.send(request);
+        // The UI may be idle, so wake it up to handle the request
+        self.ctx.request_repaint();
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play_pause(&self) {
+        self.send(MediaRequest::Action(Action::PlayPause));
+    }
+
+    fn play(&self) {
+        self.send(MediaRequest::Action(Action::Play));
+    }
+
+    fn pause(&self) {
+        self.send(MediaRequest::Action(Action::Pause));
+    }
+
+    fn stop(&self) {
+        self.send(MediaRequest::Action(Action::Stop));
+    }
+
+    fn next(&self) {
+        self.send(MediaRequest::Action(Action::Next));
+    }
+
+    fn previous(&self) {
+        self.send(MediaRequest::Action(Action::Previous));
+    }
+
+    /// Seeks by `offset` microseconds. Seeking past the end goes to the next track.
+    fn seek(&self, offset: i64) {
+        let request = {
+            let state = self.state.lock().unwrap();
+            let Some(track) = &state.track else { return };
+            let target = micros(state.position()).saturating_add(offset).max(0);
+            if track.length > Duration::ZERO && target >= micros(track.length) {
+                MediaRequest::Action(Action::Next)
+            } else {
+                MediaRequest::SeekTo(Duration::from_micros(target as u64))
+            }
+        };
+        self.send(request);
+    }
+
+    /// Seeks to `position` microseconds, if `track_id` is still the loaded track.
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let state = self.state.lock().unwrap();
+        let Some(track) = &state.track else { return };
+        if track_id.as_str() != track.object_path() || position < 0 || position > micros(track.length) {
+            return;
+        }
+        drop(state);
+        self.send(MediaRequest::SeekTo(Duration::from_micros(position as u64)));
+    }
+
+    fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported("Opening URIs is not supported".to_string()))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.state.lock().unwrap().status.as_str().to_string()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.state.lock().unwrap().metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.send(MediaRequest::SetVolume(volume.clamp(0.0, 1.0) as f32));
+    }
+
+    // Not signalled; clients poll it and listen for Seeked
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        micros(self.state.lock().unwrap().position())
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.state.lock().unwrap().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam_channel::{unbounded, Receiver};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::blocking::{Connection, MessageIterator};
+    use zbus::MatchRule;
+
+    /// A dbus-daemon of its own for each test, so nothing on the real session
+    /// bus is touched. Tests are skipped where dbus-daemon isn't installed.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .map_err(|e| eprintln!("Skipping, no dbus-daemon: {}", e))
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(Self { daemon, address: address.trim().to_string() })
+        }
+
+        fn connect(&self) -> connection::Builder<'static> {
+            connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    struct Setup {
+        mpris: Mpris,
+        client: Connection,
+        requests: Receiver<MediaRequest>,
+        _bus: PrivateBus,
+    }
+
+    fn setup() -> Option<Setup> {
+        let bus = PrivateBus::start()?;
+        let (requests_tx, requests) = unbounded();
+        let mpris = Mpris::serve(bus.connect().name(BUS_NAME).unwrap(), requests_tx, egui::Context::default()).unwrap();
+        let client = bus.connect().build().unwrap();
+        Some(Setup { mpris, client, requests, _bus: bus })
+    }
+
+    impl Setup {
+        fn call<B: serde::Serialize + zbus::zvariant::DynamicType>(&self, method: &str, body: &B) {
+            self.client.call_method(Some(BUS_NAME), OBJECT_PATH, Some(PLAYER_INTERFACE), method, body).unwrap();
+        }
+
+        fn get(&self, property: &str) -> OwnedValue {
+            let reply = self.client
+                .call_method(Some(BUS_NAME), OBJECT_PATH, Some("org.freedesktop.DBus.Properties"), "Get", &(PLAYER_INTERFACE, property))
+                .unwrap();
+            reply.body().deserialize().unwrap()
+        }
+
+        fn recv(&self) -> MediaRequest {
+            self.requests.recv_timeout(Duration::from_secs(5)).unwrap()
+        }
+    }
+
+    #[test]
+    fn methods_become_requests() {
+        let Some(bus) = setup() else { return };
+        bus.call("PlayPause", &());
+        assert_eq!(bus.recv(), MediaRequest::Action(Action::PlayPause));
+        bus.call("Next", &());
+        assert_eq!(bus.recv(), MediaRequest::Action(Action::Next));
+
+        // Nothing to seek in yet
+        bus.call("Seek", &(5_000_000i64,));
+        assert!(bus.requests.try_recv().is_err());
+
+        let path = Path::new("/music/song.mp3");
+        bus.mpris.update(Some(path), PlaybackStatus::Paused, Duration::from_secs(10), Duration::from_secs(60), 1.0);
+        bus.call("Seek", &(5_000_000i64,));
+        assert_eq!(bus.recv(), MediaRequest::SeekTo(Duration::from_secs(15)));
+        bus.call("Seek", &(-20_000_000i64,));
+        assert_eq!(bus.recv(), MediaRequest::SeekTo(Duration::ZERO));
+        bus.call("Seek", &(60_000_000i64,));
+        assert_eq!(bus.recv(), MediaRequest::Action(Action::Next));
+
+        let track_id = ObjectPath::try_from("/org/mpris/MediaPlayer2/Track/1").unwrap();
+        bus.call("SetPosition", &(track_id, 30_000_000i64));
+        assert_eq!(bus.recv(), MediaRequest::SeekTo(Duration::from_secs(30)));
+        let old_track_id = ObjectPath::try_from("/org/mpris/MediaPlayer2/Track/0").unwrap();
+        bus.call("SetPosition", &(old_track_id, 30_000_000i64));
+        assert!(bus.requests.try_recv().is_err());
+
+        bus.client
+            .call_method(Some(BUS_NAME), OBJECT_PATH, Some("org.freedesktop.DBus.Properties"), "Set", &(PLAYER_INTERFACE, "Volume", Value::from(0.25)))
+            .unwrap();
+        assert_eq!(bus.recv(), MediaRequest::SetVolume(0.25));
+    }
+
+    #[test]
+    fn publishes_state() {
+        let Some(bus) = setup() else { return };
+        assert_eq!(String::try_from(bus.get("PlaybackStatus")).unwrap(), "Stopped");
+        assert!(!bool::try_from(bus.get("CanSeek")).unwrap());
+
+        let path = Path::new("/music/My Song.mp3");
+        bus.mpris.update(Some(path), PlaybackStatus::Paused, Duration::from_secs(3), Duration::from_secs(200), 0.5);
+        assert_eq!(String::try_from(bus.get("PlaybackStatus")).unwrap(), "Paused");
+        assert_eq!(i64::try_from(bus.get("Position")).unwrap(), 3_000_000);
+        assert_eq!(f64::try_from(bus.get("Volume")).unwrap(), 0.5);
+        assert!(bool::try_from(bus.get("CanSeek")).unwrap());
+
+        let metadata: HashMap<String, OwnedValue> = bus.get("Metadata").try_into().unwrap();
+        let text = |key: &str| String::try_from(metadata[key].try_clone().unwrap()).unwrap();
+        assert_eq!(text("xesam:title"), "My Song");
+        assert_eq!(text("xesam:url"), "file:///music/My%20Song.mp3");
+        assert_eq!(i64::try_from(metadata["mpris:length"].try_clone().unwrap()).unwrap(), 200_000_000);
+
+        // Playing: the position moves on between updates
+        bus.mpris.update(Some(path), PlaybackStatus::Playing, Duration::from_secs(3), Duration::from_secs(200), 0.5);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(i64::try_from(bus.get("Position")).unwrap() >= 3_050_000);
+    }
+
+    #[test]
+    fn signals_changes_and_seeks() {
+        let Some(bus) = setup() else { return };
+        let rule = MatchRule::builder().msg_type(zbus::message::Type::Signal).path(OBJECT_PATH).unwrap().build();
+        let mut signals = MessageIterator::for_match_rule(rule, &bus.client, None).unwrap();
+
+        let path = Path::new("/music/song.mp3");
+        bus.mpris.update(Some(path), PlaybackStatus::Playing, Duration::ZERO, Duration::from_secs(60), 1.0);
+        let message = signals.next().unwrap().unwrap();
+        assert_eq!(message.header().member().unwrap().as_str(), "PropertiesChanged");
+        let (interface, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) = message.body().deserialize().unwrap();
+        assert_eq!(interface, PLAYER_INTERFACE);
+        assert!(changed.contains_key("Metadata"));
+        assert_eq!(String::try_from(changed["PlaybackStatus"].try_clone().unwrap()).unwrap(), "Playing");
+        assert!(!changed.contains_key("Volume"));
+
+        // Normal playback isn't a seek, a jump is
+        bus.mpris.update(Some(path), PlaybackStatus::Playing, Duration::from_millis(100), Duration::from_secs(60), 1.0);
+        bus.mpris.update(Some(path), PlaybackStatus::Playing, Duration::from_secs(40), Duration::from_secs(60), 1.0);
+        let message = signals.next().unwrap().unwrap();
+        assert_eq!(message.header().member().unwrap().as_str(), "Seeked");
+        assert_eq!(message.body().deserialize::<i64>().unwrap(), 40_000_000);
+    }
+}
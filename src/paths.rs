@@ -0,0 +1,101 @@
+// --- Config / Data Locations ---
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const APP_DIR_NAME: &str = "Rweb_music_player";
+
+struct Dirs {
+    config: PathBuf,
+    data: PathBuf,
+}
+
+static DIRS: OnceLock<Dirs> = OnceLock::new();
+
+// Where older versions kept their files
+fn exe_dir() -> Option<PathBuf> {
+    let mut exe_path = std::env::current_exe().ok()?;
+    exe_path.pop();
+    Some(exe_path)
+}
+
+/// Picks the directories used for the rest of the session. An explicit
+/// directory (from `--config-dir`) holds both config and data; otherwise the
+/// platform's per-user locations are used:
+/// Linux `~/.config` / `~/.local/share`, Windows `%APPDATA%`,
+/// macOS `~/Library/Application Support`.
+pub fn init(override_dir: Option<PathBuf>) {
+    let dirs = match override_dir {
+        Some(dir) => Dirs { config: dir.clone(), data: dir },
+        None => {
+            let fallback = || exe_dir().unwrap_or_default();
+            Dirs {
+                config: dirs::config_dir().map(|d| d.join(APP_DIR_NAME)).unwrap_or_else(fallback),
+                data: dirs::data_dir().map(|d| d.join(APP_DIR_NAME)).unwrap_or_else(fallback),
+            }
+        }
+    };
+    for dir in [&dirs.config, &dirs.data] {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("Failed to create {:?}: {}", dir, e);
+        }
+    }
+    let _ = DIRS.set(dirs);
+}
+
+fn dirs() -> &'static Dirs {
+    DIRS.get_or_init(|| {
+        let dir = exe_dir().unwrap_or_default();
+        Dirs { config: dir.clone(), data: dir }
+    })
+}
+
+/// Settings such as `config.json`.
+pub fn config_file(filename: &str) -> PathBuf {
+    dirs().config.join(filename)
+}
+
+/// Library data such as `playlists.json`.
+pub fn data_file(filename: &str) -> PathBuf {
+    dirs().data.join(filename)
+}
+
+/// Reads `--config-dir <dir>` or `--config-dir=<dir>` from the command line.
+pub fn config_dir_arg() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config-dir" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix("--config-dir=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// Copies files left next to the executable by older versions into the new
+/// location. Only runs when the new file doesn't exist yet, so it happens once;
+/// the originals are left in place since the install folder may be read-only.
+pub fn migrate_legacy_files() {
+    let Some(old_dir) = exe_dir() else { return };
+    let targets = [("config.json", config_file("config.json")), ("playlists.json", data_file("playlists.json"))];
+    for (filename, new_path) in targets {
+        let old_path = old_dir.join(filename);
+        if new_path.exists() || !old_path.is_file() {
+            continue;
+        }
+        // Don't carry over an empty or corrupt file in place of a fresh start
+        let readable = std::fs::read(&old_path).ok()
+            .filter(|bytes| !bytes.is_empty())
+            .is_some_and(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).is_ok());
+        if !readable {
+            eprintln!("Skipped migrating unreadable {:?}", old_path);
+            continue;
+        }
+        match std::fs::copy(&old_path, &new_path) {
+            Ok(_) => println!("Migrated {:?} to {:?}", old_path, new_path),
+            Err(e) => eprintln!("Failed to migrate {:?}: {}", old_path, e),
+        }
+    }
+}
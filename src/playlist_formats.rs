@@ -0,0 +1,397 @@
+// --- Playlist Import / Export (M3U, M3U8, PLS, XSPF) ---
+
+use super::{PlaylistItem, TrackMeta};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+pub const PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls", "xspf"];
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        Self::from_name(&ext)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u => "m3u",
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "m3u" => Some(PlaylistFormat::M3u),
+            "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the track paths of a playlist file. Relative entries are resolved
+/// against the folder the playlist lives in; URLs and blank lines are skipped.
+pub fn import_playlist(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Unsupported playlist format"))?;
+    let bytes = std::fs::read(path)?;
+    let content = String::from_utf8_lossy(&bytes);
+    Ok(parse_playlist(&content, format, path.parent().unwrap_or(Path::new(""))))
+}
+
+/// Parses playlist text, resolving relative entries against `base_dir`.
+pub fn parse_playlist(content: &str, format: PlaylistFormat, base_dir: &Path) -> Vec<PathBuf> {
+    let content = content.trim_start_matches('\u{feff}');
+    let entries = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => parse_m3u(content),
+        PlaylistFormat::Pls => parse_pls(content),
+        PlaylistFormat::Xspf => parse_xspf(content),
+    };
+    entries.into_iter().filter_map(|entry| resolve_entry(&entry, base_dir)).collect()
+}
+
+/// Writes a playlist file. `relative` stores paths relative to the playlist's
+/// folder where possible, which keeps playlists portable alongside the music.
+pub fn export_playlist(
+    path: &Path,
+    title: &str,
+    items: &[(PlaylistItem, TrackMeta)],
+    format: PlaylistFormat,
+    relative: bool,
+) -> io::Result<()> {
+    let base_dir = if relative { path.parent() } else { None };
+    std::fs::write(path, render_playlist(title, items, format, base_dir))
+}
+
+/// Playlist text in the given format. Paths under `base_dir` are written
+/// relative to it.
+pub fn render_playlist(
+    title: &str,
+    items: &[(PlaylistItem, TrackMeta)],
+    format: PlaylistFormat,
+    base_dir: Option<&Path>,
+) -> String {
+    let location = |item: &PlaylistItem| -> PathBuf {
+        if let Some(stripped) = base_dir.and_then(|dir| item.path.strip_prefix(dir).ok()) {
+            return stripped.to_path_buf();
+        }
+        item.path.clone()
+    };
+
+    let mut out = String::new();
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+            out.push_str("#EXTM3U\n");
+            for (item, meta) in items {
+                let length = if meta.duration_secs > 0 { meta.duration_secs as i64 } else { -1 };
+                out.push_str(&format!("#EXTINF:{},{}\n", length, display_title(item, meta)));
+                out.push_str(&format!("{}\n", location(item).display()));
+            }
+        }
+        PlaylistFormat::Pls => {
+            out.push_str("[playlist]\n");
+            for (i, (item, meta)) in items.iter().enumerate() {
+                let n = i + 1;
+                let length = if meta.duration_secs > 0 { meta.duration_secs as i64 } else { -1 };
+                out.push_str(&format!("File{}={}\n", n, location(item).display()));
+                out.push_str(&format!("Title{}={}\n", n, display_title(item, meta)));
+                out.push_str(&format!("Length{}={}\n", n, length));
+            }
+            out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", items.len()));
+        }
+        PlaylistFormat::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            out.push_str(&format!("  <title>{}</title>\n", xml_escape(title)));
+            out.push_str("  <trackList>\n");
+            for (item, meta) in items {
+                let loc = location(item);
+                let loc = if loc.is_absolute() { path_to_file_uri(&loc) } else { percent_encode(&loc.to_string_lossy().replace('\\', "/")) };
+                out.push_str("    <track>\n");
+                out.push_str(&format!("      <location>{}</location>\n", xml_escape(&loc)));
+                let title = if meta.title.is_empty() { &item.name } else { &meta.title };
+                out.push_str(&format!("      <title>{}</title>\n", xml_escape(title)));
+                if !meta.artist.is_empty() {
+                    out.push_str(&format!("      <creator>{}</creator>\n", xml_escape(&meta.artist)));
+                }
+                if !meta.album.is_empty() {
+                    out.push_str(&format!("      <album>{}</album>\n", xml_escape(&meta.album)));
+                }
+                if meta.duration_secs > 0 {
+                    out.push_str(&format!("      <duration>{}</duration>\n", meta.duration_secs * 1000));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+    }
+    out
+}
+
+// "Artist - Title" when tagged, the playlist name otherwise
+fn display_title(item: &PlaylistItem, meta: &TrackMeta) -> String {
+    match (meta.artist.is_empty(), meta.title.is_empty()) {
+        (false, false) => format!("{} - {}", meta.artist, meta.title),
+        (true, false) => meta.title.clone(),
+        _ => item.name.clone(),
+    }
+}
+
+fn parse_m3u(content: &str) -> Vec<String> {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_pls(content: &str) -> Vec<String> {
+    let mut entries: Vec<(usize, String)> = content.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let number = key.trim().strip_prefix("File")?.parse::<usize>().ok()?;
+            Some((number, value.trim().to_string()))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, value)| value).collect()
+}
+
+fn parse_xspf(content: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("<location>") {
+        rest = &rest[start + "<location>".len()..];
+        let Some(end) = rest.find("</location>") else { break };
+        let location = xml_unescape(rest[..end].trim());
+        // Locations are URIs; relative ones still need percent-decoding
+        if location.starts_with("file:") || location.contains("://") {
+            entries.push(location);
+        } else {
+            entries.push(percent_decode(&location));
+        }
+        rest = &rest[end..];
+    }
+    entries
+}
+
+fn resolve_entry(entry: &str, base_dir: &Path) -> Option<PathBuf> {
+    let path = if entry.starts_with("file:") {
+        file_uri_to_path(entry)?
+    } else if entry.contains("://") {
+        return None;
+    } else if cfg!(windows) {
+        PathBuf::from(entry)
+    } else {
+        // Playlists written on Windows use backslashes
+        PathBuf::from(entry.replace('\\', "/"))
+    };
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        Some(base_dir.join(path))
+    }
+}
+
+fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://").or_else(|| uri.strip_prefix("file:"))?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let decoded = percent_decode(rest);
+    // file:///C:/Music/a.mp3 -> C:/Music/a.mp3
+    let bytes = decoded.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+        return Some(PathBuf::from(decoded[1..].replace('/', "\\")));
+    }
+    Some(PathBuf::from(decoded))
+}
+
+fn path_to_file_uri(path: &Path) -> String {
+    let s = path.to_string_lossy().replace('\\', "/");
+    if s.starts_with('/') {
+        return format!("file://{}", percent_encode(&s));
+    }
+    // The drive letter keeps its colon: file:///C:/Music/a.mp3
+    match s.split_at_checked(2) {
+        Some((drive, rest)) if drive.ends_with(':') => format!("file:///{}{}", drive, percent_encode(rest)),
+        _ => format!("file:///{}", percent_encode(&s)),
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() {
+            if let Ok(b) = u8::from_str_radix(&s[i + 1..i + 3], 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An absolute folder on the platform the tests run on
+    fn root() -> PathBuf {
+        if cfg!(windows) { PathBuf::from("C:\\Music") } else { PathBuf::from("/music") }
+    }
+
+    fn item(path: PathBuf, title: &str, artist: &str, duration_secs: u64) -> (PlaylistItem, TrackMeta) {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let meta = TrackMeta { title: title.to_string(), artist: artist.to_string(), duration_secs, ..Default::default() };
+        (PlaylistItem { path, name }, meta)
+    }
+
+    #[test]
+    fn test_m3u_skips_comments_and_urls() {
+        let content = "#EXTM3U\n#EXTINF:215,Artist - Song\na.mp3\n\nhttp://example.com/stream\n#EXTINF:-1,Other\nsub/b.flac\n";
+        let paths = parse_playlist(content, PlaylistFormat::M3u, &root());
+        assert_eq!(paths, vec![root().join("a.mp3"), root().join("sub").join("b.flac")]);
+    }
+
+    #[test]
+    fn test_relative_and_absolute_entries() {
+        let absolute = root().join("x.mp3");
+        let content = format!("a.mp3\n../b.mp3\n{}\n", absolute.display());
+        let paths = parse_playlist(&content, PlaylistFormat::M3u8, &root());
+        assert_eq!(paths, vec![root().join("a.mp3"), root().join("../b.mp3"), absolute]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_windows_separators_are_converted() {
+        let paths = parse_playlist("Album\\01.mp3\n", PlaylistFormat::M3u, &root());
+        assert_eq!(paths, vec![root().join("Album/01.mp3")]);
+    }
+
+    #[test]
+    fn test_bom_and_crlf() {
+        let paths = parse_playlist("\u{feff}#EXTM3U\r\na.mp3\r\nb.mp3\r\n", PlaylistFormat::M3u, &root());
+        assert_eq!(paths, vec![root().join("a.mp3"), root().join("b.mp3")]);
+    }
+
+    #[test]
+    fn test_pls_sorted_by_file_number() {
+        let content = "[playlist]\nFile2=b.mp3\nTitle2=B\nFile1=a.mp3\nLength1=10\nFile10=c.mp3\nNumberOfEntries=3\nVersion=2\n";
+        let paths = parse_playlist(content, PlaylistFormat::Pls, &root());
+        assert_eq!(paths, vec![root().join("a.mp3"), root().join("b.mp3"), root().join("c.mp3")]);
+    }
+
+    #[test]
+    fn test_xspf_decodes_locations() {
+        let uri = path_to_file_uri(&root().join("My Song.mp3"));
+        assert!(uri.starts_with("file:///") && uri.ends_with("/My%20Song.mp3"));
+        let content = format!("<playlist><trackList>\
+            <track><location>{}</location></track>\
+            <track><location>sub/Rock%20%26%20Roll.mp3</location></track>\
+            <track><location>Tom &amp; Jerry.mp3</location></track>\
+            <track><location>https://example.com/a.mp3</location></track>\
+            </trackList></playlist>", uri);
+        let paths = parse_playlist(&content, PlaylistFormat::Xspf, &root());
+        assert_eq!(paths, vec![
+            root().join("My Song.mp3"),
+            root().join("sub").join("Rock & Roll.mp3"),
+            root().join("Tom & Jerry.mp3"),
+        ]);
+    }
+
+    #[test]
+    fn test_file_uris() {
+        assert_eq!(file_uri_to_path("file:///music/a%20b.mp3"), Some(PathBuf::from("/music/a b.mp3")));
+        assert_eq!(file_uri_to_path("file://localhost/music/a.mp3"), Some(PathBuf::from("/music/a.mp3")));
+        assert_eq!(file_uri_to_path("file:///C:/Music/a.mp3"), Some(PathBuf::from("C:\\Music\\a.mp3")));
+        assert_eq!(file_uri_to_path("file:///music/%E6%AD%8C.mp3"), Some(PathBuf::from("/music/歌.mp3")));
+        assert_eq!(file_uri_to_path("http://example.com/a.mp3"), None);
+        assert_eq!(path_to_file_uri(Path::new("C:\\Music\\a b:c.mp3")), "file:///C:/Music/a%20b%3Ac.mp3");
+    }
+
+    #[test]
+    fn test_m3u_extinf() {
+        let items = [item(root().join("a.mp3"), "Song", "Artist", 215), item(root().join("b.mp3"), "", "", 0)];
+        let text = render_playlist("List", &items, PlaylistFormat::M3u, None);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "#EXTM3U");
+        assert_eq!(lines[1], "#EXTINF:215,Artist - Song");
+        assert_eq!(lines[3], "#EXTINF:-1,b");
+    }
+
+    #[test]
+    fn test_pls_numbering() {
+        let items = [item(root().join("a.mp3"), "A", "", 10), item(root().join("b.mp3"), "B", "", 0)];
+        let text = render_playlist("List", &items, PlaylistFormat::Pls, None);
+        assert!(text.starts_with("[playlist]\n"));
+        assert!(text.contains(&format!("File2={}\n", root().join("b.mp3").display())));
+        assert!(text.contains("Title1=A\nLength1=10\n"));
+        assert!(text.contains("Length2=-1\n"));
+        assert!(text.contains("NumberOfEntries=2\n"));
+    }
+
+    #[test]
+    fn test_relative_export() {
+        let items = [item(root().join("sub").join("a.mp3"), "", "", 0)];
+        let text = render_playlist("List", &items, PlaylistFormat::M3u8, Some(&root()));
+        assert!(text.lines().any(|line| Path::new(line) == Path::new("sub").join("a.mp3")));
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let items = [
+            item(root().join("Rock & Roll").join("01 Song #1.mp3"), "Song", "Artist", 180),
+            item(root().join("歌").join("b.flac"), "", "", 0),
+            item(root().join("sub").join("a b:live.mp3"), "", "", 0),
+        ];
+        let paths: Vec<PathBuf> = items.iter().map(|(item, _)| item.path.clone()).collect();
+        for format in [PlaylistFormat::M3u, PlaylistFormat::M3u8, PlaylistFormat::Pls, PlaylistFormat::Xspf] {
+            for base_dir in [None, Some(root())] {
+                let text = render_playlist("A <list>", &items, format, base_dir.as_deref());
+                assert_eq!(parse_playlist(&text, format, &root()), paths, "{:?} {:?}", format, base_dir);
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// --- Keyboard Shortcuts ---
+
+use super::Language;
+use eframe::egui::{Key, KeyboardShortcut, ModifierNames, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const SEEK_STEP: Duration = Duration::from_secs(5);
+pub const VOLUME_STEP: f32 = 0.05;
+
+/// Something a shortcut (or a media key) can do.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    PlayPause,
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    VolumeUp,
+    VolumeDown,
+    ToggleDesktopLyrics,
+}
+
+pub const ACTIONS: [Action; 11] = [
+    Action::PlayPause,
+    Action::Play,
+    Action::Pause,
+    Action::Stop,
+    Action::Next,
+    Action::Previous,
+    Action::SeekForward,
+    Action::SeekBackward,
+    Action::VolumeUp,
+    Action::VolumeDown,
+    Action::ToggleDesktopLyrics,
+];
+
+impl Action {
+    pub fn as_str(&self, lang: Language) -> &'static str {
+        lang.tr(match self {
+            Action::PlayPause => "action.play_pause",
+            Action::Play => "action.play",
+            Action::Pause => "action.pause",
+            Action::Stop => "action.stop",
+            Action::Next => "action.next",
+            Action::Previous => "action.previous",
+            Action::SeekForward => "action.seek_forward",
+            Action::SeekBackward => "action.seek_backward",
+            Action::VolumeUp => "action.volume_up",
+            Action::VolumeDown => "action.volume_down",
+            Action::ToggleDesktopLyrics => "action.toggle_desktop_lyrics",
+        })
+    }
+
+    fn default_shortcut(&self) -> Option<&'static str> {
+        match self {
+            Action::PlayPause => Some("Space"),
+            Action::Stop => Some("S"),
+            Action::Next => Some("N"),
+            Action::Previous => Some("P"),
+            Action::SeekForward => Some("Right"),
+            Action::SeekBackward => Some("Left"),
+            // Plain up/down move through the playlist
+            Action::VolumeUp => Some("Ctrl+Up"),
+            Action::VolumeDown => Some("Ctrl+Down"),
+            Action::ToggleDesktopLyrics => Some("L"),
+            Action::Play | Action::Pause => None,
+        }
+    }
+}
+
+/// A request from outside the window: a media key or an MPRIS client. Those
+/// can also seek to a given position or set the volume.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MediaRequest {
+    Action(Action),
+    SeekTo(Duration),
+    SetVolume(f32),
+}
+
+/// Bindings as stored in config.json, e.g. `"SeekForward": "Ctrl+Right"`.
+/// Actions without an entry are unbound.
+pub type Bindings = BTreeMap<Action, String>;
+
+pub fn default_bindings() -> Bindings {
+    ACTIONS.iter()
+        .filter_map(|action| action.default_shortcut().map(|keys| (*action, keys.to_string())))
+        .collect()
+}
+
+/// Parses "Ctrl+Shift+Right". "Ctrl" means Cmd on macOS, so config files can
+/// be shared between platforms.
+pub fn parse(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = Key::from_name(parts.pop()?)?;
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" | "cmd" => Modifiers::COMMAND,
+            "alt" | "option" => Modifiers::ALT,
+            "shift" => Modifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+pub fn format(shortcut: &KeyboardShortcut) -> String {
+    shortcut.format(&ModifierNames::NAMES, false)
+}
+
+/// The shortcut for a key press, with the platform's Ctrl/Cmd folded into
+/// `command` like `parse` does.
+pub fn from_key_press(key: Key, pressed: Modifiers) -> KeyboardShortcut {
+    let mut modifiers = Modifiers::NONE;
+    if pressed.command || pressed.ctrl || pressed.mac_cmd {
+        modifiers |= Modifiers::COMMAND;
+    }
+    if pressed.alt {
+        modifiers |= Modifiers::ALT;
+    }
+    if pressed.shift {
+        modifiers |= Modifiers::SHIFT;
+    }
+    KeyboardShortcut::new(modifiers, key)
+}
+
+/// The bindings that parse, most modifiers first: egui ignores extra Shift
+/// and Alt when matching, so "Shift+Right" has to be tried before "Right".
+pub fn compile(bindings: &Bindings) -> Vec<(KeyboardShortcut, Action)> {
+    let mut compiled: Vec<_> = bindings.iter()
+        .filter_map(|(action, keys)| parse(keys).map(|shortcut| (shortcut, *action)))
+        .collect();
+    compiled.sort_by_key(|(shortcut, _)| {
+        let m = shortcut.modifiers;
+        std::cmp::Reverse(m.alt as u8 + m.shift as u8 + m.command as u8)
+    });
+    compiled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modifiers() {
+        let shortcut = parse("Ctrl+Shift+Right").unwrap();
+        assert_eq!(shortcut.logical_key, Key::ArrowRight);
+        assert_eq!(shortcut.modifiers, Modifiers::COMMAND | Modifiers::SHIFT);
+        assert_eq!(parse(" alt + Space ").unwrap(), KeyboardShortcut::new(Modifiers::ALT, Key::Space));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_names() {
+        assert_eq!(parse("Hyper+Right"), None);
+        assert_eq!(parse("Ctrl+Nope"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_ctrl_and_cmd_are_the_same() {
+        assert_eq!(parse("Ctrl+Up"), parse("Cmd+Up"));
+        let expected = parse("Ctrl+Up").unwrap();
+        assert_eq!(from_key_press(Key::ArrowUp, Modifiers::CTRL), expected);
+        assert_eq!(from_key_press(Key::ArrowUp, Modifiers::MAC_CMD), expected);
+        assert_eq!(from_key_press(Key::ArrowUp, Modifiers::COMMAND), expected);
+    }
+
+    #[test]
+    fn test_compile_tries_more_modifiers_first() {
+        let bindings = Bindings::from([
+            (Action::SeekForward, "Right".to_string()),
+            (Action::Next, "Shift+Right".to_string()),
+            (Action::VolumeUp, "Ctrl+Shift+Up".to_string()),
+            (Action::Stop, "Unknown+S".to_string()),
+        ]);
+        let actions: Vec<Action> = compile(&bindings).into_iter().map(|(_, action)| action).collect();
+        assert_eq!(actions, vec![Action::VolumeUp, Action::Next, Action::SeekForward]);
+    }
+
+    #[test]
+    fn test_default_bindings_parse() {
+        let bindings = default_bindings();
+        assert_eq!(compile(&bindings).len(), bindings.len());
+    }
+}
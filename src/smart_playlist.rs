@@ -0,0 +1,315 @@
+// --- Smart (rule-based) Playlists ---
+
+use super::{Language, TrackMeta};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SmartField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Rating,
+    Favourite,
+    PlayCount,
+    Duration,
+    AddedDays,
+    LastPlayedDays,
+}
+
+pub const SMART_FIELDS: [SmartField; 10] = [
+    SmartField::Title,
+    SmartField::Artist,
+    SmartField::Album,
+    SmartField::Genre,
+    SmartField::Rating,
+    SmartField::Favourite,
+    SmartField::PlayCount,
+    SmartField::Duration,
+    SmartField::AddedDays,
+    SmartField::LastPlayedDays,
+];
+
+impl SmartField {
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, SmartField::Title | SmartField::Artist | SmartField::Album | SmartField::Genre)
+    }
+
+    pub fn as_str(&self, lang: Language) -> &'static str {
+        lang.tr(match self {
+            SmartField::Title => "smart_field.title",
+            SmartField::Artist => "smart_field.artist",
+            SmartField::Album => "smart_field.album",
+            SmartField::Genre => "smart_field.genre",
+            SmartField::Rating => "smart_field.rating",
+            SmartField::Favourite => "smart_field.favourite",
+            SmartField::PlayCount => "smart_field.play_count",
+            SmartField::Duration => "smart_field.duration",
+            SmartField::AddedDays => "smart_field.added_days",
+            SmartField::LastPlayedDays => "smart_field.last_played_days",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SmartOp {
+    Is,
+    Contains,
+    NotContains,
+    AtLeast,
+    AtMost,
+}
+
+pub const TEXT_OPS: [SmartOp; 3] = [SmartOp::Is, SmartOp::Contains, SmartOp::NotContains];
+pub const NUMERIC_OPS: [SmartOp; 3] = [SmartOp::Is, SmartOp::AtLeast, SmartOp::AtMost];
+
+impl SmartOp {
+    pub fn as_str(&self, lang: Language) -> &'static str {
+        lang.tr(match self {
+            SmartOp::Is => "smart_op.is",
+            SmartOp::Contains => "smart_op.contains",
+            SmartOp::NotContains => "smart_op.not_contains",
+            SmartOp::AtLeast => "smart_op.at_least",
+            SmartOp::AtMost => "smart_op.at_most",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SmartSort {
+    Title,
+    MostPlayed,
+    RecentlyPlayed,
+    RecentlyAdded,
+    HighestRated,
+}
+
+pub const SMART_SORTS: [SmartSort; 5] = [
+    SmartSort::Title,
+    SmartSort::MostPlayed,
+    SmartSort::RecentlyPlayed,
+    SmartSort::RecentlyAdded,
+    SmartSort::HighestRated,
+];
+
+impl SmartSort {
+    pub fn as_str(&self, lang: Language) -> &'static str {
+        lang.tr(match self {
+            SmartSort::Title => "smart_sort.title",
+            SmartSort::MostPlayed => "smart_sort.most_played",
+            SmartSort::RecentlyPlayed => "smart_sort.recently_played",
+            SmartSort::RecentlyAdded => "smart_sort.recently_added",
+            SmartSort::HighestRated => "smart_sort.highest_rated",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SmartRule {
+    pub field: SmartField,
+    pub op: SmartOp,
+    pub value: String,
+}
+
+impl SmartRule {
+    pub fn matches(&self, meta: &TrackMeta, now: u64) -> bool {
+        let text = |s: &str| {
+            let s = s.to_lowercase();
+            let value = self.value.trim().to_lowercase();
+            match self.op {
+                SmartOp::Is => s == value,
+                SmartOp::Contains => s.contains(&value),
+                SmartOp::NotContains => !s.contains(&value),
+                SmartOp::AtLeast | SmartOp::AtMost => false,
+            }
+        };
+        let days_since = |timestamp: u64| now.saturating_sub(timestamp) / 86400;
+        let number = match self.field {
+            SmartField::Title => return text(&meta.title),
+            SmartField::Artist => return text(&meta.artist),
+            SmartField::Album => return text(&meta.album),
+            SmartField::Genre => return text(&meta.genre),
+            SmartField::Rating => meta.rating as u64,
+            SmartField::Favourite => meta.favourite as u64,
+            SmartField::PlayCount => meta.play_count as u64,
+            SmartField::Duration => meta.duration_secs,
+            // Unknown dates never match, rather than counting as "long ago"
+            SmartField::AddedDays if meta.added == 0 => return false,
+            SmartField::AddedDays => days_since(meta.added),
+            SmartField::LastPlayedDays if meta.last_played == 0 => return false,
+            SmartField::LastPlayedDays => days_since(meta.last_played),
+        };
+        let Ok(value) = self.value.trim().parse::<u64>() else { return false };
+        match self.op {
+            SmartOp::Is => number == value,
+            SmartOp::AtLeast => number >= value,
+            SmartOp::AtMost => number <= value,
+            SmartOp::Contains | SmartOp::NotContains => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SmartPlaylist {
+    pub rules: Vec<SmartRule>,
+    // All rules must match when true, any rule otherwise
+    pub match_all: bool,
+    pub sort: SmartSort,
+    pub limit: Option<usize>,
+}
+
+impl Default for SmartPlaylist {
+    fn default() -> Self {
+        Self {
+            rules: vec![SmartRule { field: SmartField::Genre, op: SmartOp::Contains, value: String::new() }],
+            match_all: true,
+            sort: SmartSort::Title,
+            limit: None,
+        }
+    }
+}
+
+impl SmartPlaylist {
+    /// Rules of the built-in favourites playlist.
+    pub fn favourites() -> Self {
+        Self {
+            rules: vec![SmartRule { field: SmartField::Favourite, op: SmartOp::Is, value: "1".to_string() }],
+            match_all: true,
+            sort: SmartSort::Title,
+            limit: None,
+        }
+    }
+
+    pub fn matches(&self, meta: &TrackMeta, now: u64) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        if self.match_all {
+            self.rules.iter().all(|rule| rule.matches(meta, now))
+        } else {
+            self.rules.iter().any(|rule| rule.matches(meta, now))
+        }
+    }
+
+    /// Orders matching tracks and applies the limit.
+    pub fn arrange<T>(&self, mut tracks: Vec<(T, &TrackMeta)>) -> Vec<T> {
+        match self.sort {
+            SmartSort::Title => tracks.sort_by_cached_key(|(_, meta)| meta.title.to_lowercase()),
+            SmartSort::MostPlayed => tracks.sort_by_key(|(_, meta)| std::cmp::Reverse(meta.play_count)),
+            SmartSort::RecentlyPlayed => tracks.sort_by_key(|(_, meta)| std::cmp::Reverse(meta.last_played)),
+            SmartSort::RecentlyAdded => tracks.sort_by_key(|(_, meta)| std::cmp::Reverse(meta.added)),
+            SmartSort::HighestRated => tracks.sort_by_key(|(_, meta)| std::cmp::Reverse(meta.rating)),
+        }
+        if let Some(limit) = self.limit {
+            tracks.truncate(limit);
+        }
+        tracks.into_iter().map(|(track, _)| track).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86400;
+    const NOW: u64 = 1_700_000_000;
+
+    fn rule(field: SmartField, op: SmartOp, value: &str) -> SmartRule {
+        SmartRule { field, op, value: value.to_string() }
+    }
+
+    fn meta(title: &str, genre: &str, rating: u8, play_count: u32) -> TrackMeta {
+        TrackMeta { title: title.to_string(), genre: genre.to_string(), rating, play_count, ..Default::default() }
+    }
+
+    #[test]
+    fn test_text_rules_ignore_case() {
+        let track = meta("Blue in Green", "Jazz", 0, 0);
+        assert!(rule(SmartField::Genre, SmartOp::Is, " jazz ").matches(&track, NOW));
+        assert!(rule(SmartField::Title, SmartOp::Contains, "GREEN").matches(&track, NOW));
+        assert!(!rule(SmartField::Title, SmartOp::NotContains, "blue").matches(&track, NOW));
+        assert!(!rule(SmartField::Title, SmartOp::AtLeast, "a").matches(&track, NOW));
+    }
+
+    #[test]
+    fn test_numeric_rules() {
+        let track = meta("", "", 4, 10);
+        assert!(rule(SmartField::Rating, SmartOp::AtLeast, "4").matches(&track, NOW));
+        assert!(!rule(SmartField::Rating, SmartOp::AtMost, "3").matches(&track, NOW));
+        assert!(rule(SmartField::PlayCount, SmartOp::Is, "10").matches(&track, NOW));
+        assert!(!rule(SmartField::PlayCount, SmartOp::Contains, "1").matches(&track, NOW));
+    }
+
+    #[test]
+    fn test_unparsable_number_never_matches() {
+        let track = meta("", "", 0, 0);
+        assert!(!rule(SmartField::Rating, SmartOp::AtMost, "five").matches(&track, NOW));
+        assert!(!rule(SmartField::Rating, SmartOp::AtLeast, "").matches(&track, NOW));
+        assert!(!rule(SmartField::Rating, SmartOp::AtLeast, "-1").matches(&track, NOW));
+    }
+
+    #[test]
+    fn test_unknown_dates_never_match() {
+        let unknown = TrackMeta::default();
+        assert!(!rule(SmartField::AddedDays, SmartOp::AtLeast, "0").matches(&unknown, NOW));
+        assert!(!rule(SmartField::LastPlayedDays, SmartOp::AtLeast, "0").matches(&unknown, NOW));
+
+        let known = TrackMeta { added: NOW - 3 * DAY, last_played: NOW - DAY / 2, ..Default::default() };
+        assert!(rule(SmartField::AddedDays, SmartOp::Is, "3").matches(&known, NOW));
+        assert!(rule(SmartField::LastPlayedDays, SmartOp::AtMost, "0").matches(&known, NOW));
+    }
+
+    #[test]
+    fn test_match_all_and_any() {
+        let track = meta("", "Jazz", 2, 0);
+        let mut smart = SmartPlaylist {
+            rules: vec![rule(SmartField::Genre, SmartOp::Is, "jazz"), rule(SmartField::Rating, SmartOp::AtLeast, "4")],
+            match_all: true,
+            ..Default::default()
+        };
+        assert!(!smart.matches(&track, NOW));
+        smart.match_all = false;
+        assert!(smart.matches(&track, NOW));
+    }
+
+    #[test]
+    fn test_no_rules_match_everything() {
+        let smart = SmartPlaylist { rules: Vec::new(), match_all: false, ..Default::default() };
+        assert!(smart.matches(&TrackMeta::default(), NOW));
+    }
+
+    #[test]
+    fn test_favourites() {
+        let favourite = TrackMeta { favourite: true, ..Default::default() };
+        assert!(SmartPlaylist::favourites().matches(&favourite, NOW));
+        assert!(!SmartPlaylist::favourites().matches(&TrackMeta::default(), NOW));
+    }
+
+    #[test]
+    fn test_arrange_sorts_and_limits() {
+        let tracks = [meta("b", "", 3, 5), meta("A", "", 5, 1), meta("c", "", 1, 9)];
+        let input = || tracks.iter().enumerate().collect::<Vec<(usize, &TrackMeta)>>();
+        let arrange = |sort, limit| SmartPlaylist { sort, limit, ..Default::default() }.arrange(input());
+
+        assert_eq!(arrange(SmartSort::Title, None), vec![1, 0, 2]);
+        assert_eq!(arrange(SmartSort::MostPlayed, None), vec![2, 0, 1]);
+        assert_eq!(arrange(SmartSort::HighestRated, None), vec![1, 0, 2]);
+        assert_eq!(arrange(SmartSort::MostPlayed, Some(2)), vec![2, 0]);
+        assert_eq!(arrange(SmartSort::Title, Some(0)), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_arrange_recent_first_keeps_ties_in_order() {
+        let tracks = [
+            TrackMeta { added: 10, last_played: 0, ..Default::default() },
+            TrackMeta { added: 30, last_played: 50, ..Default::default() },
+            TrackMeta { added: 10, last_played: 40, ..Default::default() },
+        ];
+        let input = || tracks.iter().enumerate().collect::<Vec<(usize, &TrackMeta)>>();
+        let arrange = |sort| SmartPlaylist { sort, ..Default::default() }.arrange(input());
+
+        assert_eq!(arrange(SmartSort::RecentlyAdded), vec![1, 0, 2]);
+        assert_eq!(arrange(SmartSort::RecentlyPlayed), vec![1, 2, 0]);
+    }
+}
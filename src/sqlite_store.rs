@@ -0,0 +1,513 @@
+// --- SQLite Storage (feature "sqlite") ---
+
+use super::{paths, HistoryEntry, PlaylistItem, PlaylistsManager, TrackMeta, PLAYLISTS_VERSION};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Bump and add a step to `migrate` when the schema changes
+const SCHEMA_VERSION: i32 = 4;
+
+pub fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    // The HTTP API and the UI save from different threads
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    if version < 1 {
+        tx.execute_batch(
+            "CREATE TABLE meta (
+                 key TEXT PRIMARY KEY,
+                 value TEXT NOT NULL
+             );
+             CREATE TABLE playlists (
+                 name TEXT PRIMARY KEY
+             );
+             CREATE TABLE playlist_items (
+                 playlist TEXT NOT NULL,
+                 position INTEGER NOT NULL,
+                 path TEXT NOT NULL,
+                 name TEXT NOT NULL,
+                 PRIMARY KEY (playlist, position)
+             );
+             CREATE TABLE library (
+                 path TEXT PRIMARY KEY,
+                 title TEXT NOT NULL,
+                 artist TEXT NOT NULL,
+                 album TEXT NOT NULL,
+                 genre TEXT NOT NULL,
+                 duration_secs INTEGER NOT NULL,
+                 rating INTEGER NOT NULL,
+                 play_count INTEGER NOT NULL,
+                 last_played INTEGER NOT NULL,
+                 added INTEGER NOT NULL
+             );",
+        )?;
+    }
+    if version < 2 {
+        tx.execute_batch(
+            "CREATE TABLE history (
+                 timestamp INTEGER NOT NULL,
+                 path TEXT NOT NULL,
+                 listened_secs INTEGER NOT NULL,
+                 skipped INTEGER NOT NULL,
+                 PRIMARY KEY (timestamp, path)
+             );",
+        )?;
+    }
+    if version < 3 {
+        tx.execute_batch("ALTER TABLE library ADD COLUMN favourite INTEGER NOT NULL DEFAULT 0;")?;
+    }
+    if version < 4 {
+        // Fractional positions, so inserting an item doesn't renumber the rest
+        tx.execute_batch(
+            "CREATE TABLE playlist_items_v4 (
+                 playlist TEXT NOT NULL,
+                 position REAL NOT NULL,
+                 path TEXT NOT NULL,
+                 name TEXT NOT NULL,
+                 PRIMARY KEY (playlist, position)
+             );
+             INSERT INTO playlist_items_v4 SELECT playlist, position, path, name FROM playlist_items;
+             DROP TABLE playlist_items;
+             ALTER TABLE playlist_items_v4 RENAME TO playlist_items;",
+        )?;
+    }
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()
+}
+
+fn get_meta<T: serde::de::DeserializeOwned>(conn: &Connection, key: &str) -> rusqlite::Result<Option<T>> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+        .optional()?;
+    Ok(value.and_then(|v| serde_json::from_str(&v).ok()))
+}
+
+fn set_meta<T: serde::Serialize>(tx: &Transaction, key: &str, value: &T) -> rusqlite::Result<()> {
+    let json = serde_json::to_string(value).unwrap_or_default();
+    tx.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value WHERE value != excluded.value",
+        params![key, json],
+    )?;
+    Ok(())
+}
+
+/// What the database currently holds, so `save` only writes what changed.
+#[derive(Default)]
+pub struct Saved {
+    lists: HashMap<String, Vec<(f64, PlaylistItem)>>,
+    library: HashMap<PathBuf, TrackMeta>,
+}
+
+/// Reads everything back into a `PlaylistsManager`. Returns `None` for a
+/// fresh database so the caller can import the JSON file instead.
+pub fn load(conn: &Connection) -> rusqlite::Result<Option<(PlaylistsManager, Saved)>> {
+    let Some(current_name) = get_meta::<String>(conn, "current_name")? else {
+        return Ok(None);
+    };
+
+    let mut saved_lists: HashMap<String, Vec<(f64, PlaylistItem)>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT name FROM playlists")?;
+    for name in stmt.query_map([], |row| row.get::<_, String>(0))? {
+        saved_lists.insert(name?, Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT playlist, position, path, name FROM playlist_items ORDER BY playlist, position")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+    })?;
+    for row in rows {
+        let (playlist, position, path, name) = row?;
+        saved_lists.entry(playlist).or_default().push((position, PlaylistItem { path: PathBuf::from(path), name }));
+    }
+    let lists = saved_lists.iter()
+        .map(|(name, items)| (name.clone(), items.iter().map(|(_, item)| item.clone()).collect()))
+        .collect();
+
+    let mut library = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT path, title, artist, album, genre, duration_secs, rating, favourite, play_count, last_played, added FROM library",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            PathBuf::from(row.get::<_, String>(0)?),
+            TrackMeta {
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                album: row.get(3)?,
+                genre: row.get(4)?,
+                duration_secs: row.get::<_, i64>(5)? as u64,
+                rating: row.get(6)?,
+                favourite: row.get(7)?,
+                play_count: row.get(8)?,
+                last_played: row.get::<_, i64>(9)? as u64,
+                added: row.get::<_, i64>(10)? as u64,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (path, meta) = row?;
+        library.insert(path, meta);
+    }
+
+    let mut stmt = conn.prepare("SELECT path, timestamp, listened_secs, skipped FROM history ORDER BY timestamp")?;
+    let history = stmt
+        .query_map([], |row| {
+            Ok(HistoryEntry {
+                path: PathBuf::from(row.get::<_, String>(0)?),
+                timestamp: row.get::<_, i64>(1)? as u64,
+                listened_secs: row.get::<_, i64>(2)? as u64,
+                skipped: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let data = PlaylistsManager {
+        version: PLAYLISTS_VERSION,
+        current_name,
+        lists,
+        library: library.clone(),
+        sort_orders: get_meta(conn, "sort_orders")?.unwrap_or_default(),
+        queue: get_meta(conn, "queue")?.unwrap_or_default(),
+        smart: get_meta(conn, "smart")?.unwrap_or_default(),
+        history,
+        added_unknown: Default::default(),
+    };
+    Ok(Some((data, Saved { lists: saved_lists, library })))
+}
+
+/// Positions for `count` items between `lo` and `hi` (either may be open), or
+/// `None` once the gap is too small to split further.
+fn positions_between(lo: Option<f64>, hi: Option<f64>, count: usize) -> Option<Vec<f64>> {
+    let positions: Vec<f64> = (0..count)
+        .map(|i| match (lo, hi) {
+            (None, None) => i as f64,
+            (Some(lo), None) => lo + (i + 1) as f64,
+            (None, Some(hi)) => hi - (count - i) as f64,
+            (Some(lo), Some(hi)) => lo + (hi - lo) * (i + 1) as f64 / (count + 1) as f64,
+        })
+        .collect();
+    let bounds = lo.into_iter().chain(positions.iter().copied()).chain(hi);
+    let increasing = bounds.clone().zip(bounds.skip(1)).all(|(a, b)| a < b);
+    increasing.then_some(positions)
+}
+
+/// Writes one playlist, touching only the rows between the unchanged head and
+/// tail of the list. Returns what the table now holds for it.
+fn save_playlist(
+    tx: &Transaction,
+    name: &str,
+    old: &[(f64, PlaylistItem)],
+    items: &[PlaylistItem],
+) -> rusqlite::Result<Vec<(f64, PlaylistItem)>> {
+    let head = old.iter().zip(items).take_while(|((_, a), b)| a == *b).count();
+    let tail = old[head..].iter().rev().zip(items[head..].iter().rev()).take_while(|((_, a), b)| a == *b).count();
+    let removed = &old[head..old.len() - tail];
+    let added = &items[head..items.len() - tail];
+
+    let mut delete = tx.prepare_cached("DELETE FROM playlist_items WHERE playlist = ?1 AND position = ?2")?;
+    let mut insert = tx.prepare_cached("INSERT INTO playlist_items (playlist, position, path, name) VALUES (?1, ?2, ?3, ?4)")?;
+
+    let lo = head.checked_sub(1).map(|i| old[i].0);
+    let hi = old.get(old.len() - tail).filter(|_| tail > 0).map(|(position, _)| *position);
+    let Some(positions) = positions_between(lo, hi, added.len()) else {
+        // Ran out of room between two neighbours: renumber the whole list
+        tx.execute("DELETE FROM playlist_items WHERE playlist = ?1", [name])?;
+        for (position, item) in items.iter().enumerate() {
+            insert.execute(params![name, position as f64, item.path.to_string_lossy(), item.name])?;
+        }
+        return Ok(items.iter().enumerate().map(|(i, item)| (i as f64, item.clone())).collect());
+    };
+
+    for (position, _) in removed {
+        delete.execute(params![name, position])?;
+    }
+    for (position, item) in positions.iter().zip(added) {
+        insert.execute(params![name, position, item.path.to_string_lossy(), item.name])?;
+    }
+    let mut saved = old[..head].to_vec();
+    saved.extend(positions.into_iter().zip(added.iter().cloned()));
+    saved.extend_from_slice(&old[old.len() - tail..]);
+    Ok(saved)
+}
+
+/// Writes what changed since the last load or save in one transaction.
+/// `saved` is only updated once the transaction has committed.
+pub fn save(conn: &mut Connection, saved: &mut Saved, data: &PlaylistsManager) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    set_meta(&tx, "current_name", &data.current_name)?;
+    set_meta(&tx, "sort_orders", &data.sort_orders)?;
+    set_meta(&tx, "queue", &data.queue)?;
+    set_meta(&tx, "smart", &data.smart)?;
+
+    let mut lists = HashMap::new();
+    for name in saved.lists.keys().filter(|name| !data.lists.contains_key(*name)) {
+        tx.execute("DELETE FROM playlist_items WHERE playlist = ?1", [name])?;
+        tx.execute("DELETE FROM playlists WHERE name = ?1", [name])?;
+    }
+    for (name, items) in &data.lists {
+        let old = match saved.lists.get(name) {
+            Some(old) => old.as_slice(),
+            None => {
+                tx.execute("INSERT OR IGNORE INTO playlists (name) VALUES (?1)", [name])?;
+                &[]
+            }
+        };
+        let unchanged = old.len() == items.len() && old.iter().zip(items).all(|((_, a), b)| a == b);
+        if !unchanged || !saved.lists.contains_key(name) {
+            lists.insert(name.clone(), save_playlist(&tx, name, old, items)?);
+        }
+    }
+
+    {
+        let mut delete = tx.prepare_cached("DELETE FROM library WHERE path = ?1")?;
+        for path in saved.library.keys().filter(|path| !data.library.contains_key(*path)) {
+            delete.execute([path.to_string_lossy()])?;
+        }
+        let mut upsert = tx.prepare_cached(
+            "INSERT OR REPLACE INTO library (path, title, artist, album, genre, duration_secs, rating, favourite, play_count, last_played, added)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for (path, meta) in &data.library {
+            if saved.library.get(path) == Some(meta) {
+                continue;
+            }
+            upsert.execute(params![
+                path.to_string_lossy(),
+                meta.title,
+                meta.artist,
+                meta.album,
+                meta.genre,
+                meta.duration_secs as i64,
+                meta.rating,
+                meta.favourite,
+                meta.play_count,
+                meta.last_played as i64,
+                meta.added as i64,
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    saved.lists.retain(|name, _| data.lists.contains_key(name));
+    saved.lists.extend(lists);
+    if saved.library != data.library {
+        saved.library = data.library.clone();
+    }
+    Ok(())
+}
+
+/// Logs one listen, dropping entries older than `oldest`.
+pub fn append_history(conn: &Connection, entry: &HistoryEntry, oldest: u64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO history (timestamp, path, listened_secs, skipped) VALUES (?1, ?2, ?3, ?4)",
+        params![entry.timestamp as i64, entry.path.to_string_lossy(), entry.listened_secs as i64, entry.skipped],
+    )?;
+    conn.execute("DELETE FROM history WHERE timestamp < ?1", [oldest as i64])?;
+    Ok(())
+}
+
+/// Replaces the whole history, e.g. after an import.
+pub fn replace_history(conn: &mut Connection, history: &[HistoryEntry]) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM history", [])?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT OR IGNORE INTO history (timestamp, path, listened_secs, skipped) VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        for entry in history {
+            insert.execute(params![entry.timestamp as i64, entry.path.to_string_lossy(), entry.listened_secs as i64, entry.skipped])?;
+        }
+    }
+    tx.commit()
+}
+
+// One connection for the process along with what it holds; `None` if the
+// database couldn't be opened, in which case the caller keeps using
+// playlists.json
+static CONNECTION: Mutex<Option<(Connection, Saved)>> = Mutex::new(None);
+
+/// Opens `library.db` in the data directory and loads it.
+pub fn load_db() -> rusqlite::Result<Option<PlaylistsManager>> {
+    let conn = open(&paths::data_file("library.db"))?;
+    let (data, saved) = match load(&conn)? {
+        Some((data, saved)) => (Some(data), saved),
+        None => (None, Saved::default()),
+    };
+    *CONNECTION.lock().unwrap() = Some((conn, saved));
+    Ok(data)
+}
+
+/// Saves to the open database. Returns `None` if no database is in use.
+pub fn save_db(data: &PlaylistsManager) -> Option<rusqlite::Result<()>> {
+    let mut conn = CONNECTION.lock().unwrap();
+    conn.as_mut().map(|(conn, saved)| save(conn, saved, data))
+}
+
+/// Logs a listen in the open database. Returns `None` if no database is in use.
+pub fn append_history_db(entry: &HistoryEntry, oldest: u64) -> Option<rusqlite::Result<()>> {
+    let conn = CONNECTION.lock().unwrap();
+    conn.as_ref().map(|(conn, _)| append_history(conn, entry, oldest))
+}
+
+/// Replaces the history in the open database. Returns `None` if no database is in use.
+pub fn replace_history_db(history: &[HistoryEntry]) -> Option<rusqlite::Result<()>> {
+    let mut conn = CONNECTION.lock().unwrap();
+    conn.as_mut().map(|(conn, _)| replace_history(conn, history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn memory_db() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn
+    }
+
+    fn item(path: &str) -> PlaylistItem {
+        PlaylistItem { path: PathBuf::from(path), name: path.to_string() }
+    }
+
+    fn sample() -> PlaylistsManager {
+        let json = r#"{
+            "version": 3,
+            "current_name": "Mix",
+            "lists": {
+                "Mix": [{"path": "/m/a.mp3", "name": "a"}, {"path": "/m/b.flac", "name": "b"}],
+                "Empty": []
+            },
+            "library": {
+                "/m/a.mp3": {"title": "A", "artist": "X", "duration_secs": 200, "rating": 4, "favourite": true, "play_count": 3, "last_played": 1700000100, "added": 1700000000},
+                "/m/b.flac": {"title": "B", "album": "Y", "genre": "Jazz", "added": 1700000050}
+            },
+            "sort_orders": {},
+            "queue": ["/m/b.flac"],
+            "smart": {},
+            "history": [
+                {"path": "/m/a.mp3", "timestamp": 1700000100, "listened_secs": 200, "skipped": false},
+                {"path": "/m/b.flac", "timestamp": 1700000300, "listened_secs": 10, "skipped": true}
+            ]
+        }"#;
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn as_json(data: &PlaylistsManager) -> Value {
+        serde_json::to_value(data).unwrap()
+    }
+
+    fn positions(conn: &Connection, playlist: &str) -> Vec<(f64, String)> {
+        conn.prepare("SELECT position, path FROM playlist_items WHERE playlist = ?1 ORDER BY position")
+            .unwrap()
+            .query_map([playlist], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fresh_database_loads_nothing() {
+        assert!(load(&memory_db()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let data = sample();
+        let mut conn = memory_db();
+        save(&mut conn, &mut Saved::default(), &data).unwrap();
+        let (loaded, _) = load(&conn).unwrap().unwrap();
+        assert_eq!(as_json(&loaded), as_json(&data));
+    }
+
+    #[test]
+    fn test_edits_round_trip() {
+        let mut data = sample();
+        let mut conn = memory_db();
+        let mut saved = Saved::default();
+        save(&mut conn, &mut saved, &data).unwrap();
+
+        let mix = data.lists.get_mut("Mix").unwrap();
+        mix.insert(0, item("/m/c.ogg"));
+        mix.insert(2, item("/m/d.wav"));
+        mix.swap(1, 3);
+        data.lists.remove("Empty");
+        data.lists.insert("New".to_string(), vec![item("/m/a.mp3")]);
+        data.library.remove(&PathBuf::from("/m/b.flac"));
+        data.library.get_mut(&PathBuf::from("/m/a.mp3")).unwrap().play_count += 1;
+        data.current_name = "New".to_string();
+        save(&mut conn, &mut saved, &data).unwrap();
+
+        let (loaded, _) = load(&conn).unwrap().unwrap();
+        assert_eq!(as_json(&loaded), as_json(&data));
+    }
+
+    #[test]
+    fn test_insert_keeps_other_positions() {
+        let mut data = sample();
+        let mut conn = memory_db();
+        let mut saved = Saved::default();
+        save(&mut conn, &mut saved, &data).unwrap();
+        let before = positions(&conn, "Mix");
+
+        data.lists.get_mut("Mix").unwrap().insert(0, item("/m/c.ogg"));
+        save(&mut conn, &mut saved, &data).unwrap();
+        let after = positions(&conn, "Mix");
+
+        assert_eq!(after.len(), 3);
+        assert_eq!(after[0].1, "/m/c.ogg");
+        assert_eq!(&after[1..], &before[..]);
+    }
+
+    #[test]
+    fn test_renumbers_when_out_of_room() {
+        let mut data = sample();
+        let mut conn = memory_db();
+        let mut saved = Saved::default();
+        save(&mut conn, &mut saved, &data).unwrap();
+
+        // Each insert halves the gap between the first two items
+        for i in 0..100 {
+            data.lists.get_mut("Mix").unwrap().insert(1, item(&format!("/m/{}.mp3", i)));
+            save(&mut conn, &mut saved, &data).unwrap();
+        }
+        let (loaded, _) = load(&conn).unwrap().unwrap();
+        assert_eq!(loaded.lists["Mix"], data.lists["Mix"]);
+    }
+
+    #[test]
+    fn test_history_append_and_replace() {
+        let data = sample();
+        let mut conn = memory_db();
+        save(&mut conn, &mut Saved::default(), &data).unwrap();
+        replace_history(&mut conn, &data.history).unwrap();
+
+        let entry = HistoryEntry { path: PathBuf::from("/m/c.ogg"), timestamp: 1700000500, listened_secs: 90, skipped: false };
+        // Drops the first entry, which is older than the second
+        append_history(&conn, &entry, data.history[1].timestamp).unwrap();
+
+        let (loaded, _) = load(&conn).unwrap().unwrap();
+        assert_eq!(loaded.history, vec![data.history[1].clone(), entry]);
+    }
+
+    #[test]
+    fn test_positions_between() {
+        assert_eq!(positions_between(None, None, 2), Some(vec![0.0, 1.0]));
+        assert_eq!(positions_between(Some(3.0), None, 2), Some(vec![4.0, 5.0]));
+        assert_eq!(positions_between(None, Some(0.0), 2), Some(vec![-2.0, -1.0]));
+        assert_eq!(positions_between(Some(0.0), Some(1.0), 1), Some(vec![0.5]));
+        assert_eq!(positions_between(Some(1.0), Some(1.0 + f64::EPSILON), 1), None);
+    }
+}
@@ -0,0 +1,316 @@
+// --- Crash-safe JSON Storage ---
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Number of rotating backups kept next to each file (file.bak1 is the newest)
+pub const BACKUP_COUNT: usize = 3;
+
+// Files written by a newer version of the app. They are left alone, so
+// downgrading and upgrading again doesn't lose anything.
+static READ_ONLY: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Files already backed up this session. Rotating on every save would let a
+// burst of saves (e.g. the library indexer) push good copies out of the ring.
+static BACKED_UP: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".bak{}", n))
+}
+
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    for n in (1..BACKUP_COUNT).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            std::fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Writes to a temporary file, flushes it to disk and renames it over the
+/// target, so a crash leaves either the old or the new file, never half of one.
+/// The app's own files also get rotating backups, see `replace_file` for others.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if READ_ONLY.lock().unwrap().iter().any(|p| p == path) {
+        return Err(io::Error::other("the file was written by a newer version and is left unchanged"));
+    }
+
+    let first_save = {
+        let mut backed_up = BACKED_UP.lock().unwrap();
+        if backed_up.iter().any(|p| p == path) {
+            false
+        } else {
+            backed_up.push(path.to_path_buf());
+            true
+        }
+    };
+    if first_save {
+        if let Err(e) = rotate_backups(path) {
+            eprintln!("Failed to back up {:?}: {}", path, e);
+        }
+    }
+
+    replace_file(path, bytes)
+}
+
+/// Replaces a file through a temporary file like `write_atomic`, without the
+/// backups. For the user's files, such as an .lrc next to the music.
+pub fn replace_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Appends a line to a log file, creating it if needed. Much cheaper than
+/// rewriting a large file, though the file gets no backups.
+pub fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", line).as_bytes())?;
+    file.sync_data()
+}
+
+pub fn save_json<T: serde::Serialize>(path: &Path, value: &T, pretty: bool) -> io::Result<()> {
+    let bytes = if pretty { serde_json::to_vec_pretty(value) } else { serde_json::to_vec(value) }
+        .map_err(io::Error::other)?;
+    write_atomic(path, &bytes)
+}
+
+/// Reads and migrates a versioned JSON file without any backup handling.
+pub fn read_versioned<T: DeserializeOwned>(
+    path: &Path,
+    current_version: u32,
+    migrate: fn(Value, u32) -> Value,
+) -> Result<T, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    from_versioned_value(value, current_version, migrate)
+}
+
+/// Migrates an already parsed versioned document, e.g. one sent over HTTP.
+pub fn from_versioned_value<T: DeserializeOwned>(
+    value: Value,
+    current_version: u32,
+    migrate: fn(Value, u32) -> Value,
+) -> Result<T, String> {
+    if !value.is_object() {
+        return Err("expected a JSON object".to_string());
+    }
+    // Files written before versioning was introduced have no version field
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+    if version > current_version {
+        return Err(format!("written by a newer version (schema {} > {})", version, current_version));
+    }
+    let value = if version < current_version { migrate(value, version) } else { value };
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+// The schema version of a file that is valid JSON but too new to read
+fn newer_version(path: &Path, current_version: u32) -> Option<u64> {
+    let bytes = std::fs::read(path).ok()?;
+    let value: Value = serde_json::from_slice(&bytes).ok()?;
+    value.get("version").and_then(Value::as_u64).filter(|v| *v > current_version as u64)
+}
+
+/// Loads a versioned JSON file, migrating older schemas forward.
+///
+/// Returns `(None, None)` if the file doesn't exist or is empty. A file from a
+/// newer version is left in place and never overwritten this session. If the
+/// file can't be read otherwise, it is moved aside, the newest readable backup
+/// is used instead and a message describing what happened is returned alongside.
+pub fn load_versioned<T: DeserializeOwned>(
+    path: &Path,
+    current_version: u32,
+    migrate: fn(Value, u32) -> Value,
+) -> (Option<T>, Option<String>) {
+    // An empty file is what a crash between create and write leaves behind
+    if std::fs::metadata(path).map_or(true, |m| m.len() == 0) {
+        return (None, None);
+    }
+    let error = match read_versioned(path, current_version, migrate) {
+        Ok(value) => return (Some(value), None),
+        Err(e) => e,
+    };
+
+    if let Some(version) = newer_version(path, current_version) {
+        READ_ONLY.lock().unwrap().push(path.to_path_buf());
+        let message = format!(
+            "{:?} was written by a newer version of the app (schema {} > {}). \
+             It was left unchanged and nothing will be saved to it; starting with defaults.",
+            path, version, current_version
+        );
+        return (None, Some(message));
+    }
+
+    let broken = with_suffix(path, &format!(".broken-{}", super::unix_now()));
+    let moved = std::fs::rename(path, &broken).is_ok();
+    let mut message = format!("Failed to load {:?}: {}", path, error);
+    if moved {
+        message.push_str(&format!("\nThe file was kept as {:?}.", broken));
+    }
+
+    for n in 1..=BACKUP_COUNT {
+        let backup = backup_path(path, n);
+        if !backup.exists() {
+            continue;
+        }
+        if let Ok(value) = read_versioned(&backup, current_version, migrate) {
+            message.push_str(&format!("\nRestored from backup {:?}.", backup));
+            return (Some(value), Some(message));
+        }
+    }
+    message.push_str("\nNo usable backup was found; starting with defaults.");
+    (None, Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Doc {
+        version: u32,
+        items: Vec<String>,
+    }
+
+    // v1 had no items
+    fn migrate(mut value: Value, from: u32) -> Value {
+        if from < 2 {
+            value["items"] = serde_json::json!(["migrated"]);
+        }
+        value["version"] = 2.into();
+        value
+    }
+
+    fn load(path: &Path) -> (Option<Doc>, Option<String>) {
+        load_versioned(path, 2, migrate)
+    }
+
+    // A fresh folder per test; the statics remember paths for the whole run
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rweb_storage_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_backs_up_once_per_session() {
+        let path = temp_dir("once").join("data.json");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::write(backup_path(&path, 1), "older").unwrap();
+        write_atomic(&path, b"new 1").unwrap();
+        write_atomic(&path, b"new 2").unwrap();
+        assert_eq!(read(&path), "new 2");
+        assert_eq!(read(&backup_path(&path, 1)), "old");
+        assert_eq!(read(&backup_path(&path, 2)), "older");
+        assert!(!backup_path(&path, 3).exists());
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn test_replace_file_keeps_no_backups() {
+        let path = temp_dir("replace").join("song.lrc");
+        std::fs::write(&path, "old").unwrap();
+        replace_file(&path, b"new").unwrap();
+        assert_eq!(read(&path), "new");
+        assert!(!backup_path(&path, 1).exists());
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn test_loads_and_migrates() {
+        let dir = temp_dir("migrate");
+        let current = dir.join("current.json");
+        std::fs::write(&current, r#"{"version": 2, "items": ["a"]}"#).unwrap();
+        assert_eq!(load(&current), (Some(Doc { version: 2, items: vec!["a".to_string()] }), None));
+
+        // Files from before versioning count as version 1
+        let unversioned = dir.join("unversioned.json");
+        std::fs::write(&unversioned, "{}").unwrap();
+        assert_eq!(load(&unversioned), (Some(Doc { version: 2, items: vec!["migrated".to_string()] }), None));
+    }
+
+    #[test]
+    fn test_missing_or_empty_file() {
+        let dir = temp_dir("empty");
+        assert_eq!(load(&dir.join("missing.json")), (None, None));
+        let empty = dir.join("empty.json");
+        std::fs::write(&empty, "").unwrap();
+        assert_eq!(load(&empty), (None, None));
+        // Left in place rather than moved aside as broken
+        assert!(empty.exists());
+    }
+
+    #[test]
+    fn test_restores_newest_readable_backup() {
+        let path = temp_dir("restore").join("data.json");
+        std::fs::write(&path, "{ cut off").unwrap();
+        std::fs::write(backup_path(&path, 1), "also broken").unwrap();
+        std::fs::write(backup_path(&path, 2), r#"{"version": 2, "items": ["two"]}"#).unwrap();
+        std::fs::write(backup_path(&path, 3), r#"{"version": 2, "items": ["three"]}"#).unwrap();
+
+        let (doc, message) = load(&path);
+        assert_eq!(doc.unwrap().items, vec!["two".to_string()]);
+        assert!(message.unwrap().contains(".bak2"));
+        // The broken file is kept aside for inspection
+        assert!(!path.exists());
+        let dir = path.parent().unwrap();
+        assert!(std::fs::read_dir(dir).unwrap().any(|e| e.unwrap().file_name().to_string_lossy().starts_with("data.json.broken-")));
+    }
+
+    #[test]
+    fn test_no_usable_backup() {
+        let path = temp_dir("no_backup").join("data.json");
+        std::fs::write(&path, "[1, 2").unwrap();
+        let (doc, message) = load(&path);
+        assert_eq!(doc, None);
+        assert!(message.unwrap().contains("No usable backup"));
+    }
+
+    #[test]
+    fn test_newer_version_is_left_alone() {
+        let path = temp_dir("newer").join("data.json");
+        let newer = r#"{"version": 9, "items": [], "added_later": true}"#;
+        std::fs::write(&path, newer).unwrap();
+
+        let (doc, message) = load(&path);
+        assert_eq!(doc, None);
+        assert!(message.unwrap().contains("newer version"));
+        assert!(write_atomic(&path, b"{}").is_err());
+        assert_eq!(read(&path), newer);
+        assert!(!backup_path(&path, 1).exists());
+    }
+
+    #[test]
+    fn test_versioned_value_must_be_an_object() {
+        assert!(from_versioned_value::<Doc>(serde_json::json!([1]), 2, migrate).is_err());
+        assert!(from_versioned_value::<Doc>(serde_json::json!({"version": 3, "items": []}), 2, migrate).is_err());
+    }
+}
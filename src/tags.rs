@@ -0,0 +1,317 @@
+// --- Tag Reading / Writing ---
+
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::FileType;
+use lofty::id3::v2::{CommentFrame, Frame, FrameId, Id3v2Tag, TextInformationFrame, UnsynchronizedTextFrame};
+use lofty::iff::wav::WavFile;
+use lofty::mpeg::MpegFile;
+use lofty::picture::{Picture, PictureType};
+use lofty::prelude::{AudioFile, ItemKey, TagExt, TaggedFileExt};
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+use lofty::TextEncoding;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+// Text fields shown in the tag editor. Year and track stay strings so they can
+// be edited (and left blank) like the others.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct TagFields {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub album_artist: String,
+    pub genre: String,
+    pub year: String,
+    pub track: String,
+    pub comment: String,
+    // Unsynchronised lyrics (USLT in ID3v2); LRC text is stored as-is
+    pub lyrics: String,
+}
+
+impl TagFields {
+    const NAMES: [&'static str; 9] = ["title", "artist", "album", "album_artist", "genre", "year", "track", "comment", "lyrics"];
+
+    // In the order of NAMES
+    fn values(&self) -> [&String; 9] {
+        [&self.title, &self.artist, &self.album, &self.album_artist, &self.genre, &self.year, &self.track, &self.comment, &self.lyrics]
+    }
+
+    fn values_mut(&mut self) -> [&mut String; 9] {
+        [
+            &mut self.title,
+            &mut self.artist,
+            &mut self.album,
+            &mut self.album_artist,
+            &mut self.genre,
+            &mut self.year,
+            &mut self.track,
+            &mut self.comment,
+            &mut self.lyrics,
+        ]
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CoverInfo {
+    pub mime_type: String,
+    pub size: usize,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverChange {
+    Replace(PathBuf),
+    Remove,
+}
+
+// Fields left as `None` are not touched; `Some("")` removes the field
+#[derive(Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct TagChanges {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<String>,
+    pub track: Option<String>,
+    pub comment: Option<String>,
+    pub lyrics: Option<String>,
+    pub cover: Option<CoverChange>,
+}
+
+impl TagChanges {
+    /// Changes that turn `original` into `edited`.
+    pub fn between(original: &TagFields, edited: &TagFields) -> Self {
+        let changed = |a: &String, b: &String| if a != b { Some(b.clone()) } else { None };
+        Self {
+            title: changed(&original.title, &edited.title),
+            artist: changed(&original.artist, &edited.artist),
+            album: changed(&original.album, &edited.album),
+            album_artist: changed(&original.album_artist, &edited.album_artist),
+            genre: changed(&original.genre, &edited.genre),
+            year: changed(&original.year, &edited.year),
+            track: changed(&original.track, &edited.track),
+            comment: changed(&original.comment, &edited.comment),
+            lyrics: changed(&original.lyrics, &edited.lyrics),
+            cover: None,
+        }
+    }
+
+    /// Marks a field (by its `TagFields::NAMES` name) for removal, unless it
+    /// already has a new value.
+    pub fn clear(&mut self, name: &str) {
+        let field = match name {
+            "title" => &mut self.title,
+            "artist" => &mut self.artist,
+            "album" => &mut self.album,
+            "album_artist" => &mut self.album_artist,
+            "genre" => &mut self.genre,
+            "year" => &mut self.year,
+            "track" => &mut self.track,
+            "comment" => &mut self.comment,
+            "lyrics" => &mut self.lyrics,
+            _ => return,
+        };
+        field.get_or_insert_with(String::new);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn text_changes(&self) -> [(ItemKey, &Option<String>); 9] {
+        [
+            (ItemKey::TrackTitle, &self.title),
+            (ItemKey::TrackArtist, &self.artist),
+            (ItemKey::AlbumTitle, &self.album),
+            (ItemKey::AlbumArtist, &self.album_artist),
+            (ItemKey::Genre, &self.genre),
+            (ItemKey::Year, &self.year),
+            (ItemKey::TrackNumber, &self.track),
+            (ItemKey::Comment, &self.comment),
+            (ItemKey::Lyrics, &self.lyrics),
+        ]
+    }
+}
+
+pub fn read_tags(path: &Path) -> lofty::error::Result<(TagFields, Option<CoverInfo>)> {
+    let tagged_file = Probe::open(path)?.read()?;
+    let Some(tag) = tagged_file.primary_tag().or_else(|| tagged_file.first_tag()) else {
+        return Ok((TagFields::default(), None));
+    };
+    let get = |key: ItemKey| tag.get_string(&key).unwrap_or_default().to_string();
+    let fields = TagFields {
+        title: get(ItemKey::TrackTitle),
+        artist: get(ItemKey::TrackArtist),
+        album: get(ItemKey::AlbumTitle),
+        album_artist: get(ItemKey::AlbumArtist),
+        genre: get(ItemKey::Genre),
+        year: get(ItemKey::Year),
+        track: get(ItemKey::TrackNumber),
+        comment: get(ItemKey::Comment),
+        lyrics: get(ItemKey::Lyrics),
+    };
+    let cover = front_cover(tag).map(|picture| CoverInfo {
+        mime_type: picture.mime_type().map(|m| m.as_str().to_string()).unwrap_or_default(),
+        size: picture.data().len(),
+    });
+    Ok((fields, cover))
+}
+
+fn front_cover(tag: &Tag) -> Option<&Picture> {
+    tag.pictures().iter()
+        .find(|p| p.pic_type() == PictureType::CoverFront)
+        .or_else(|| tag.pictures().first())
+}
+
+/// Applies the changes to the file's primary tag, creating one if needed.
+pub fn write_tags(path: &Path, changes: &TagChanges) -> lofty::error::Result<()> {
+    if let Some(mut tag) = read_id3v2(path)? {
+        write_id3v2_changes(&mut tag, changes)?;
+        return tag.save_to_path(path, WriteOptions::default());
+    }
+
+    let mut tagged_file = Probe::open(path)?.read()?;
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else { return Ok(()) };
+
+    for (key, value) in changes.text_changes() {
+        match value.as_deref().map(str::trim) {
+            Some("") => tag.remove_key(&key),
+            Some(value) => {
+                tag.insert_text(key, value.to_string());
+            }
+            None => {}
+        }
+    }
+    match &changes.cover {
+        Some(CoverChange::Replace(image_path)) => {
+            let picture = read_cover(image_path)?;
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(picture);
+        }
+        Some(CoverChange::Remove) => {
+            while !tag.pictures().is_empty() {
+                tag.remove_picture(0);
+            }
+        }
+        None => {}
+    }
+    tag.save_to_path(path, WriteOptions::default())
+}
+
+fn read_cover(image_path: &Path) -> lofty::error::Result<Picture> {
+    let mut reader = std::fs::File::open(image_path)?;
+    let mut picture = Picture::from_reader(&mut reader)?;
+    picture.set_pic_type(PictureType::CoverFront);
+    Ok(picture)
+}
+
+/// The ID3v2 tag of an MP3 or WAV file (empty if it has none yet), or `None`
+/// for other file types.
+///
+/// Those files are edited through their ID3v2 frames. Going through the
+/// generic Tag would drop the language and description of comments and lyrics
+/// along with any frame it has no mapping for, and WAV files may only have a
+/// RIFF INFO chunk, which can't hold most fields.
+pub fn read_id3v2(path: &Path) -> lofty::error::Result<Option<Id3v2Tag>> {
+    let file_type = Probe::open(path)?.guess_file_type()?.file_type();
+    let mut file = std::fs::File::open(path)?;
+    let tag = match file_type {
+        Some(FileType::Mpeg) => MpegFile::read_from(&mut file, ParseOptions::new())?.id3v2().cloned(),
+        Some(FileType::Wav) => WavFile::read_from(&mut file, ParseOptions::new())?.id3v2().cloned(),
+        _ => return Ok(None),
+    };
+    Ok(Some(tag.unwrap_or_default()))
+}
+
+fn write_id3v2_changes(tag: &mut Id3v2Tag, changes: &TagChanges) -> lofty::error::Result<()> {
+    let text_frames = [
+        ("TIT2", &changes.title),
+        ("TPE1", &changes.artist),
+        ("TALB", &changes.album),
+        ("TPE2", &changes.album_artist),
+        ("TCON", &changes.genre),
+        ("TDRC", &changes.year),
+        ("TRCK", &changes.track),
+    ];
+    for (id, value) in text_frames {
+        let Some(value) = value.as_deref().map(str::trim) else { continue };
+        let id = FrameId::Valid(Cow::Borrowed(id));
+        tag.remove(&id).for_each(drop);
+        if !value.is_empty() {
+            tag.insert(Frame::Text(TextInformationFrame::new(id, TextEncoding::UTF8, value.to_string())));
+        }
+    }
+    if let Some(comment) = &changes.comment {
+        set_described_text(tag, "COMM", comment.trim());
+    }
+    if let Some(lyrics) = &changes.lyrics {
+        set_described_text(tag, "USLT", lyrics.trim());
+    }
+    match &changes.cover {
+        Some(CoverChange::Replace(image_path)) => {
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.insert_picture(read_cover(image_path)?);
+        }
+        Some(CoverChange::Remove) => tag.remove(&FrameId::Valid(Cow::Borrowed("APIC"))).for_each(drop),
+        None => {}
+    }
+    Ok(())
+}
+
+// Sets the COMM or USLT frame without a description, which is what players
+// show, keeping its language and encoding. Frames with a description belong to
+// other software and are left alone. An empty value removes the frame.
+fn set_described_text(tag: &mut Id3v2Tag, id: &str, value: &str) {
+    let frames: Vec<Frame<'static>> = tag.remove(&FrameId::Valid(Cow::Borrowed(id))).collect();
+    let mut updated = false;
+    for mut frame in frames {
+        let content = match &mut frame {
+            Frame::Comment(comment) if comment.description.is_empty() => Some(&mut comment.content),
+            Frame::UnsynchronizedText(lyrics) if lyrics.description.is_empty() => Some(&mut lyrics.content),
+            _ => None,
+        };
+        if let Some(content) = content {
+            if value.is_empty() {
+                continue;
+            }
+            if !updated {
+                *content = value.to_string();
+                updated = true;
+            }
+        }
+        tag.insert(frame);
+    }
+    if !updated && !value.is_empty() {
+        // Language unknown, as lofty itself writes it
+        let (language, description, content) = (*b"XXX", String::new(), value.to_string());
+        tag.insert(match id {
+            "COMM" => Frame::Comment(CommentFrame::new(TextEncoding::UTF8, language, description, content)),
+            _ => Frame::UnsynchronizedText(UnsynchronizedTextFrame::new(TextEncoding::UTF8, language, description, content)),
+        });
+    }
+}
+
+/// Fields shared by all files; fields that differ come back empty and are
+/// listed in the second value so the editor can show them as mixed.
+pub fn common_fields(all: &[TagFields]) -> (TagFields, Vec<&'static str>) {
+    let Some(first) = all.first() else { return (TagFields::default(), Vec::new()) };
+    let values: Vec<[&String; 9]> = all.iter().map(TagFields::values).collect();
+    let mut common = first.clone();
+    let mut mixed = Vec::new();
+    for (i, value) in common.values_mut().into_iter().enumerate() {
+        if values.iter().any(|v| *v[i] != *value) {
+            value.clear();
+            mixed.push(TagFields::NAMES[i]);
+        }
+    }
+    (common, mixed)
+}
@@ -0,0 +1,131 @@
+// --- Themes ---
+
+use eframe::egui;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::ops::RangeInclusive;
+
+// Ranges offered in the settings. Values from a hand-edited config or an
+// imported theme are clamped to them, since egui takes NaN or negative
+// spacing as-is.
+pub const ROUNDING_RANGE: RangeInclusive<u8> = 0..=12;
+pub const SPACING_RANGE: RangeInclusive<f32> = 0.5..=2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ThemeBase {
+    Dark,
+    Light,
+}
+
+/// A named set of UI colours and sizes, applied on top of egui's dark or
+/// light style. Stored in config.json and exported as plain JSON.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub base: ThemeBase,
+    // Main window panels
+    pub background: [u8; 4],
+    // Selection, slider fill and links
+    pub accent: [u8; 4],
+    pub text: [u8; 4],
+    // Corner radius of buttons and windows, in points
+    #[serde(deserialize_with = "clamped_rounding")]
+    pub rounding: u8,
+    // Multiplier for the gaps between and inside widgets
+    #[serde(deserialize_with = "clamped_spacing")]
+    pub spacing: f32,
+}
+
+fn clamped_rounding<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    Ok(u8::deserialize(deserializer)?.min(*ROUNDING_RANGE.end()))
+}
+
+fn clamped_spacing<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let spacing = f32::deserialize(deserializer)?;
+    Ok(if spacing.is_finite() { spacing.clamp(*SPACING_RANGE.start(), *SPACING_RANGE.end()) } else { 1.0 })
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "Dark".to_string(),
+            base: ThemeBase::Dark,
+            background: [30, 30, 30, 255],
+            accent: [0, 92, 128, 255],
+            text: [140, 140, 140, 255],
+            rounding: 2,
+            spacing: 1.0,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: "Light".to_string(),
+            base: ThemeBase::Light,
+            background: [248, 248, 248, 255],
+            accent: [144, 209, 255, 255],
+            text: [80, 80, 80, 255],
+            rounding: 2,
+            spacing: 1.0,
+        }
+    }
+
+    /// Presets that are always available and can't be overwritten.
+    pub fn built_in() -> [Theme; 2] {
+        [Self::dark(), Self::light()]
+    }
+
+    pub fn background(&self) -> egui::Color32 {
+        color(self.background)
+    }
+
+    /// Replaces the visuals and spacing in `style`. Starts from egui's own
+    /// defaults each time, so applying it every frame doesn't compound.
+    pub fn apply(&self, style: &mut egui::Style) {
+        let mut visuals = match self.base {
+            ThemeBase::Dark => egui::Visuals::dark(),
+            ThemeBase::Light => egui::Visuals::light(),
+        };
+        let accent = color(self.accent);
+        visuals.panel_fill = self.background();
+        visuals.selection.bg_fill = accent;
+        // Selected text needs to stand out against the accent
+        visuals.selection.stroke.color = if egui::Rgba::from(accent).intensity() > 0.5 {
+            egui::Color32::BLACK
+        } else {
+            egui::Color32::WHITE
+        };
+        visuals.hyperlink_color = accent;
+        visuals.widgets.noninteractive.fg_stroke.color = color(self.text);
+        visuals.widgets.inactive.fg_stroke.color = color(self.text);
+
+        let radius = egui::CornerRadius::same(self.rounding);
+        visuals.window_corner_radius = radius;
+        visuals.menu_corner_radius = radius;
+        for widget in [
+            &mut visuals.widgets.noninteractive,
+            &mut visuals.widgets.inactive,
+            &mut visuals.widgets.hovered,
+            &mut visuals.widgets.active,
+            &mut visuals.widgets.open,
+        ] {
+            widget.corner_radius = radius;
+        }
+        style.visuals = visuals;
+
+        let defaults = egui::style::Spacing::default();
+        style.spacing.item_spacing = defaults.item_spacing * self.spacing;
+        style.spacing.button_padding = defaults.button_padding * self.spacing;
+    }
+}
+
+/// A colour as stored in config.json.
+pub fn color([r, g, b, a]: [u8; 4]) -> egui::Color32 {
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}